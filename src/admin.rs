@@ -0,0 +1,254 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    ops::Range,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use lazy_static::lazy_static;
+use rdkafka::Message;
+use serde::{Deserialize, Serialize};
+use crate::{
+    diff_store::update_diff_store,
+    error::Error,
+    kafka::{
+        create_replay_consumer, create_sr_settings, handle_message, key_partition,
+        scan_partitions, topic_partitions, MessageHandler, ReplayStart,
+    },
+    reconcile::{EventReader, KafkaEventReader, LatestEvents},
+    schema_cache::CachingAvroDecoder,
+};
+
+lazy_static! {
+    pub static ref ADMIN_API_KEY: Option<String> = env::var("ADMIN_API_KEY").ok();
+    static ref REPLAY_STATUS: Mutex<ReplayStatus> = Mutex::new(ReplayStatus::default());
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReplayRequest {
+    /// Partitions to replay, all partitions of the input topic if omitted.
+    pub partitions: Option<Vec<i32>>,
+    pub start: ReplayStart,
+}
+
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayState {
+    #[default]
+    Idle,
+    Running,
+    Finished,
+    Failed,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PartitionProgress {
+    pub start: i64,
+    pub end: i64,
+    pub position: i64,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ReplayStatus {
+    pub state: ReplayState,
+    pub job: Option<String>,
+    pub started_at: Option<u128>,
+    pub finished_at: Option<u128>,
+    pub partitions: BTreeMap<i32, PartitionProgress>,
+    pub processed: u64,
+    pub failed: u64,
+    pub error: Option<String>,
+}
+
+#[post("/admin/replay")]
+pub async fn start_replay(
+    request: HttpRequest,
+    body: web::Json<ReplayRequest>,
+    http_client: web::Data<reqwest::Client>,
+) -> HttpResponse {
+    if !authorized(&request) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    if !try_start_job(format!("replay {:?}", body.start)) {
+        return HttpResponse::Conflict().json(replay_status());
+    }
+
+    let ReplayRequest { partitions, start } = body.into_inner();
+    let http_client = http_client.get_ref().clone();
+    tokio::spawn(async move {
+        finish_job(replay_range(partitions, start, http_client).await);
+    });
+    HttpResponse::Accepted().json(replay_status())
+}
+
+#[post("/admin/reprocess/{fdk_id}")]
+pub async fn start_reprocess(
+    request: HttpRequest,
+    fdk_id: web::Path<String>,
+    http_client: web::Data<reqwest::Client>,
+) -> HttpResponse {
+    if !authorized(&request) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    let fdk_id = fdk_id.into_inner();
+    if !try_start_job(format!("reprocess {}", fdk_id)) {
+        return HttpResponse::Conflict().json(replay_status());
+    }
+
+    let http_client = http_client.get_ref().clone();
+    tokio::spawn(async move {
+        finish_job(reprocess_fdk_id(fdk_id, http_client).await);
+    });
+    HttpResponse::Accepted().json(replay_status())
+}
+
+#[get("/admin/replay/status")]
+pub async fn get_replay_status(request: HttpRequest) -> HttpResponse {
+    if !authorized(&request) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    HttpResponse::Ok().json(replay_status())
+}
+
+pub fn replay_status() -> ReplayStatus {
+    lock_status().clone()
+}
+
+fn authorized(request: &HttpRequest) -> bool {
+    match (ADMIN_API_KEY.as_ref(), request.headers().get("X-API-KEY")) {
+        (Some(key), Some(header)) => header.as_bytes() == key.as_bytes(),
+        _ => false,
+    }
+}
+
+fn lock_status() -> std::sync::MutexGuard<'static, ReplayStatus> {
+    REPLAY_STATUS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default()
+}
+
+fn try_start_job(job: String) -> bool {
+    let mut status = lock_status();
+    if status.state == ReplayState::Running {
+        return false;
+    }
+
+    tracing::info!(job, "starting admin job");
+    *status = ReplayStatus {
+        state: ReplayState::Running,
+        job: Some(job),
+        started_at: Some(now_millis()),
+        ..Default::default()
+    };
+    true
+}
+
+fn finish_job(result: Result<(), Error>) {
+    let mut status = lock_status();
+    status.finished_at = Some(now_millis());
+    match result {
+        Ok(_) => {
+            tracing::info!(
+                processed = status.processed,
+                failed = status.failed,
+                "admin job finished"
+            );
+            status.state = ReplayState::Finished;
+        }
+        Err(e) => {
+            tracing::error!(error = e.to_string(), "admin job failed");
+            status.state = ReplayState::Failed;
+            status.error = Some(e.to_string());
+        }
+    }
+}
+
+/// Keeps the replay status up to date while scanning with the wrapped handler.
+struct StatusTracking<H>(H);
+
+impl<H: MessageHandler + Send> MessageHandler for StatusTracking<H> {
+    fn assigned(&mut self, ranges: &BTreeMap<i32, Range<i64>>) {
+        lock_status().partitions = ranges
+            .iter()
+            .map(|(partition, range)| {
+                let progress = PartitionProgress {
+                    start: range.start,
+                    end: range.end,
+                    position: range.start,
+                };
+                (*partition, progress)
+            })
+            .collect();
+        self.0.assigned(ranges);
+    }
+
+    async fn handle(&mut self, message: &(impl Message + Sync)) -> Result<(), Error> {
+        let result = self.0.handle(message).await;
+
+        let mut status = lock_status();
+        match result {
+            Ok(_) => status.processed += 1,
+            Err(_) => status.failed += 1,
+        }
+        if let Some(progress) = status.partitions.get_mut(&message.partition()) {
+            progress.position = message.offset() + 1;
+        }
+        result
+    }
+}
+
+struct ReplayHandler<'a> {
+    decoder: CachingAvroDecoder<'a>,
+    http_client: reqwest::Client,
+}
+
+impl MessageHandler for ReplayHandler<'_> {
+    async fn handle(&mut self, message: &(impl Message + Sync)) -> Result<(), Error> {
+        handle_message(&mut self.decoder, message, &self.http_client).await
+    }
+}
+
+async fn replay_range(
+    partitions: Option<Vec<i32>>,
+    start: ReplayStart,
+    http_client: reqwest::Client,
+) -> Result<(), Error> {
+    let mut handler = StatusTracking(ReplayHandler {
+        decoder: CachingAvroDecoder::new(create_sr_settings()?),
+        http_client,
+    });
+    scan_partitions(partitions, start, &mut handler).await
+}
+
+/// Reprocesses the latest event of `fdk_id`. Only the partition its key is
+/// produced to is scanned, unless no event is found there, e.g. because the
+/// topic was produced to with another partitioner, in which case all
+/// partitions are scanned.
+async fn reprocess_fdk_id(fdk_id: String, http_client: reqwest::Client) -> Result<(), Error> {
+    let partition_count = topic_partitions(&create_replay_consumer()?)?.len() as i32;
+    let mut handler = StatusTracking(LatestEvents::new(Some(fdk_id.clone()))?);
+    if partition_count > 0 {
+        let partition = key_partition(fdk_id.as_bytes(), partition_count);
+        scan_partitions(Some(vec![partition]), ReplayStart::Beginning, &mut handler).await?;
+    }
+    if !handler.0.events.contains_key(&fdk_id) {
+        tracing::info!(fdk_id, "no event in key partition, scanning all partitions");
+        scan_partitions(None, ReplayStart::Beginning, &mut handler).await?;
+    }
+
+    match handler.0.events.remove(&fdk_id) {
+        Some(latest) => {
+            let event = KafkaEventReader::new()?
+                .read(latest.partition, latest.offset)
+                .await?;
+            update_diff_store(event, &http_client).await
+        }
+        None => Err(Error::NotFound(format!("event for {}", fdk_id))),
+    }
+}
@@ -1,13 +1,53 @@
-use actix_web::{get, App, HttpServer, Responder};
+use std::path::PathBuf;
+
+use actix_web::{get, web, App, HttpServer, Responder};
+use clap::{Parser, Subcommand};
 use futures::{
     stream::{FuturesUnordered, StreamExt},
     FutureExt,
 };
 use fdk_rdf_postman::{
-    kafka::{create_sr_settings, run_async_processor},
+    admin::{get_replay_status, start_replay, start_reprocess},
+    coalesce::check_coalescing,
+    concurrency::check_adaptive_concurrency,
+    diff_store::{check_oversized_graph_policy, create_http_client},
+    output::check_transactional_id_prefix,
+    error::Error,
+    kafka::{create_sr_settings, run_async_processor, WORKERS},
     metrics::{get_metrics, register_metrics},
+    reconcile::reconcile,
+    replay::replay,
+    telemetry::init_tracing,
+    transform::{install_transformations, TransformationPipeline},
+    validation::load_shapes,
 };
 
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Feed events from a JSONL file, an Avro container file or a directory of
+    /// Turtle files through the diff store pipeline.
+    Replay {
+        path: PathBuf,
+        /// Print the intended diff store requests instead of sending them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Compare the latest state of every fdkId on the input topic with the
+    /// graphs in the diff store, and report missing, orphaned and stale graphs.
+    Reconcile {
+        /// Re-post missing and stale graphs, and delete orphaned graphs.
+        #[arg(long)]
+        repair: bool,
+    },
+}
+
 #[get("/ping")]
 async fn ping() -> impl Responder {
     "pong"
@@ -31,22 +71,98 @@ async fn metrics() -> impl Responder {
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt()
-        .json()
-        .with_max_level(tracing::Level::INFO)
-        .with_target(false)
-        .with_current_span(false)
-        .init();
+    let tracer_provider = init_tracing();
+    check_configuration().unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "invalid configuration");
+        std::process::exit(1);
+    });
+
+    let exit_code = match Cli::parse().command {
+        None => {
+            serve().await;
+            0
+        }
+        Some(Command::Replay { path, dry_run }) => {
+            match replay(&path, dry_run, &http_client()).await {
+                Ok(summary) => {
+                    tracing::info!(
+                        succeeded = summary.succeeded,
+                        failed = summary.failed,
+                        "replay finished"
+                    );
+                    i32::from(summary.failed > 0)
+                }
+                Err(e) => {
+                    tracing::error!(error = e.to_string(), "replay failed");
+                    1
+                }
+            }
+        }
+        Some(Command::Reconcile { repair }) => {
+            match reconcile(repair, &http_client()).await {
+                Ok(report) => {
+                    match serde_json::to_string_pretty(&report) {
+                        Ok(report) => println!("{}", report),
+                        Err(e) => tracing::error!(error = e.to_string(), "unable to serialize report"),
+                    }
+                    i32::from(report.repair_failures > 0 || !report.unchecked.is_empty())
+                }
+                Err(e) => {
+                    tracing::error!(error = e.to_string(), "reconciliation failed");
+                    1
+                }
+            }
+        }
+    };
+
+    // Flush spans that are not yet exported.
+    if let Err(e) = tracer_provider.shutdown() {
+        tracing::warn!(error = e.to_string(), "unable to shut down tracer provider");
+    }
+    std::process::exit(exit_code);
+}
+
+/// Fails on configuration that can not work, before any message is handled.
+fn check_configuration() -> Result<(), Error> {
+    // Custom graph transformations are registered here, by adding them to
+    // the configured ones with `TransformationPipeline::with`.
+    install_transformations(TransformationPipeline::from_env()?)?;
+    load_shapes()?;
+    check_oversized_graph_policy()?;
+    check_transactional_id_prefix()?;
+    check_coalescing()?;
+    check_adaptive_concurrency()?;
+    Ok(())
+}
 
+fn http_client() -> reqwest::Client {
+    create_http_client().unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "http client creation error");
+        std::process::exit(1);
+    })
+}
+
+async fn serve() {
     register_metrics();
 
     let sr_settings = create_sr_settings().unwrap_or_else(|e| {
         tracing::error!(error = e.to_string(), "sr settings creation error");
         std::process::exit(1);
     });
+    let http_client = http_client();
 
+    let admin_http_client = web::Data::new(http_client.clone());
     let http_server = tokio::spawn(
-        HttpServer::new(|| App::new().service(ping).service(ready).service(metrics))
+        HttpServer::new(move || {
+            App::new()
+                .app_data(admin_http_client.clone())
+                .service(ping)
+                .service(ready)
+                .service(metrics)
+                .service(start_replay)
+                .service(start_reprocess)
+                .service(get_replay_status)
+        })
             .bind(("0.0.0.0", 8080))
             .unwrap_or_else(|e| {
                 tracing::error!(error = e.to_string(), "server error");
@@ -56,8 +172,8 @@ async fn main() {
             .map(|f| f.map_err(|e| e.into())),
     );
 
-    (0..4)
-        .map(|i| tokio::spawn(run_async_processor(i, sr_settings.clone())))
+    (0..*WORKERS)
+        .map(|i| tokio::spawn(run_async_processor(i, sr_settings.clone(), http_client.clone())))
         .chain(std::iter::once(http_server))
         .collect::<FuturesUnordered<_>>()
         .for_each(|result| async {
@@ -0,0 +1,23 @@
+use std::{env, net::TcpListener};
+
+use fdk_rdf_postman::mock_diff_store::MockDiffStore;
+
+/// Runs the in-memory mock diff store, e.g. for local development against a
+/// docker compose Kafka setup.
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let port: u16 = env::var("MOCK_DIFF_STORE_PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(8090);
+    let mock = MockDiffStore::start_on(TcpListener::bind(("0.0.0.0", port))?)?;
+    if let Ok(api_key) = env::var("DIFF_STORE_KEY") {
+        mock.require_api_key(&api_key);
+    }
+    println!("mock diff store listening on {}", mock.url());
+
+    tokio::signal::ctrl_c().await?;
+    mock.stop().await;
+    println!("stopped with {} graphs stored", mock.graphs().len());
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use std::{env, future::Future};
+use lazy_static::lazy_static;
+use reqwest::header::CONTENT_TYPE;
+use sha2::{Digest, Sha256};
+use crate::error::Error;
+
+lazy_static! {
+    pub static ref BLOB_STORE_URL: Option<String> =
+        env::var("BLOB_STORE_URL").ok().filter(|url| !url.is_empty());
+    pub static ref BLOB_STORE_KEY: Option<String> =
+        env::var("BLOB_STORE_KEY").ok().filter(|key| !key.is_empty());
+}
+
+/// Storage for payloads that are too large to be sent inline. `put` returns a
+/// reference that is sent in place of the payload, so the store must be
+/// reachable by the receiver of the reference.
+pub trait BlobStore {
+    fn put(
+        &self,
+        key: &str,
+        content: &[u8],
+        content_type: &str,
+    ) -> impl Future<Output = Result<String, Error>> + Send;
+}
+
+/// Blob store accepting payloads with `PUT {url}/{name}`, e.g. an object
+/// storage bucket. The reference to a payload is its URL.
+pub struct HttpBlobStore {
+    url: String,
+    api_key: Option<String>,
+    http_client: reqwest::Client,
+}
+
+impl HttpBlobStore {
+    pub fn new(url: &str, api_key: Option<String>, http_client: reqwest::Client) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            api_key,
+            http_client,
+        }
+    }
+
+    /// The blob store configured with `BLOB_STORE_URL`, if any.
+    pub fn from_env(http_client: &reqwest::Client) -> Option<Self> {
+        BLOB_STORE_URL
+            .as_ref()
+            .map(|url| Self::new(url, BLOB_STORE_KEY.clone(), http_client.clone()))
+    }
+}
+
+impl BlobStore for HttpBlobStore {
+    async fn put(&self, key: &str, content: &[u8], content_type: &str) -> Result<String, Error> {
+        // The content hash makes the name unique per payload, so that a blob
+        // is never overwritten while a reference to it may still be read.
+        let hash = Sha256::digest(content);
+        let name = format!("{}-{:x}", key.replace(['/', '\\'], "_"), hash);
+        let url = format!("{}/{}", self.url, name);
+
+        let mut request = self
+            .http_client
+            .put(&url)
+            .header(CONTENT_TYPE, content_type)
+            .body(content.to_vec());
+        if let Some(api_key) = &self.api_key {
+            request = request.header("X-API-KEY", api_key);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(url)
+        } else {
+            Err(Error::SinkStatus {
+                message: format!("Invalid response from blob store for {}", key),
+                status,
+                body: response.text().await.unwrap_or_default(),
+            })
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::{collections::HashMap, env, time::Duration};
+use lazy_static::lazy_static;
+use rdkafka::Message;
+use tracing::Instrument;
+use crate::{
+    diff_store::{event_to_action, DiffStoreAction, POSTMAN_TYPE},
+    error::Error,
+    kafka::{acknowledge, decode_message, handle_received, receive_span, RETRY_DELAY},
+    metrics::COALESCED_EVENTS,
+    output::{Output, KAFKA_TRANSACTIONS},
+    schema_cache::CachingAvroDecoder,
+    schemas::InputEvent,
+    source::MessageSource,
+};
+
+lazy_static! {
+    pub static ref COALESCE_WINDOW: Option<Duration> = env::var("COALESCE_WINDOW_MS")
+        .ok()
+        .and_then(|window| window.parse().ok())
+        .filter(|window| *window > 0)
+        .map(Duration::from_millis);
+    pub static ref COALESCE_MAX_MESSAGES: usize = env::var("COALESCE_MAX_MESSAGES")
+        .ok()
+        .and_then(|max| max.parse().ok())
+        .unwrap_or(1000);
+}
+
+/// Fails when coalescing is configured together with Kafka transactions,
+/// which commit every message in a transaction of its own.
+pub fn check_coalescing() -> Result<(), Error> {
+    if COALESCE_WINDOW.is_some() && *KAFKA_TRANSACTIONS {
+        return Err(Error::Configuration(
+            "COALESCE_WINDOW_MS can not be combined with KAFKA_TRANSACTIONS".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Handles messages from `source` in batches collected for up to `window`,
+/// applying only the newest event of each fdkId in a batch. Events that do not
+/// change the diff store, and messages that can not be decoded, are handled
+/// like without coalescing.
+///
+/// Messages are acknowledged in order, and only once the event that
+/// superseded them is applied. When an event fails, its partition is rewound
+/// to the first message not acknowledged, so that no offset is committed past
+/// an event that was not applied.
+pub async fn run_coalescing_processor<S: MessageSource>(
+    worker_id: usize,
+    source: &S,
+    decoder: &mut CachingAvroDecoder<'_>,
+    http_client: &reqwest::Client,
+    output: &Output,
+    window: Duration,
+) -> Result<(), Error> {
+    loop {
+        let mut batch = Vec::new();
+        let received = receive_batch(source, window, &mut batch).await;
+        if !batch.is_empty() {
+            handle_batch(worker_id, source, decoder, http_client, output, &batch).await;
+        }
+        if !received? {
+            return Ok(());
+        }
+    }
+}
+
+/// Receives messages until `window` has passed since the first one, or the
+/// batch is full. Returns `false` if the source is exhausted.
+async fn receive_batch<'a, S: MessageSource>(
+    source: &'a S,
+    window: Duration,
+    batch: &mut Vec<S::Message<'a>>,
+) -> Result<bool, Error> {
+    let Some(first) = source.recv().await? else {
+        return Ok(false);
+    };
+    batch.push(first);
+
+    let deadline = tokio::time::Instant::now() + window;
+    while batch.len() < *COALESCE_MAX_MESSAGES {
+        match tokio::time::timeout_at(deadline, source.recv()).await {
+            Ok(received) => match received? {
+                Some(message) => batch.push(message),
+                // Messages may be received again if the batch is rewound.
+                None => break,
+            },
+            Err(_) => break,
+        }
+    }
+    Ok(true)
+}
+
+async fn handle_batch<S: MessageSource>(
+    worker_id: usize,
+    source: &S,
+    decoder: &mut CachingAvroDecoder<'_>,
+    http_client: &reqwest::Client,
+    output: &Output,
+    batch: &[S::Message<'_>],
+) {
+    // The fdkId of every message with an event that changes the diff store,
+    // and the index of the newest such message for each fdkId. Decoded events
+    // are kept only until superseded, so that at most one graph per fdkId is
+    // held in memory.
+    let spans: Vec<_> = batch.iter().map(|message| receive_span(worker_id, message)).collect();
+    let mut decoded = Vec::with_capacity(batch.len());
+    let mut fdk_ids = Vec::with_capacity(batch.len());
+    let mut newest: HashMap<String, (i64, usize)> = HashMap::new();
+    for (index, message) in batch.iter().enumerate() {
+        let result = decode_message(decoder, message)
+            .instrument(spans[index].clone())
+            .await;
+        let fdk_id = match &result {
+            Ok(InputEvent::HarvestEvent(event))
+                if !matches!(event_to_action(event.event_type), DiffStoreAction::Nothing) =>
+            {
+                let entry = newest.entry(event.fdk_id.clone()).or_insert((event.timestamp, index));
+                if event.timestamp >= entry.0 {
+                    // The event of the previous newest message is superseded.
+                    if let Some(superseded) = decoded.get_mut(entry.1) {
+                        *superseded = None;
+                    }
+                    *entry = (event.timestamp, index);
+                }
+                Some(event.fdk_id.clone())
+            }
+            _ => None,
+        };
+        decoded.push(Some(result));
+        fdk_ids.push(fdk_id);
+    }
+    let newest_index = |index: usize| match &fdk_ids[index] {
+        Some(fdk_id) => newest[fdk_id].1,
+        None => index,
+    };
+
+    let mut handled = vec![false; batch.len()];
+    for (index, message) in batch.iter().enumerate() {
+        if newest_index(index) != index {
+            tracing::debug!(
+                offset = message.offset(),
+                fdk_id = fdk_ids[index],
+                "event superseded by newer event"
+            );
+            COALESCED_EVENTS.with_label_values(&[POSTMAN_TYPE.label()]).inc();
+            continue;
+        }
+        let Some(result) = decoded[index].take() else {
+            continue;
+        };
+        handled[index] = handle_received(result, message, http_client, output)
+            .instrument(spans[index].clone())
+            .await;
+    }
+
+    // Acknowledge each partition up to its first message that was not
+    // handled, and rewind the partition to that message.
+    let mut rewound: Vec<&S::Message<'_>> = Vec::new();
+    for (index, message) in batch.iter().enumerate() {
+        if rewound.iter().any(|rewound| rewound.partition() == message.partition()) {
+            continue;
+        }
+        if handled[newest_index(index)] {
+            acknowledge(source, message, output).await;
+        } else {
+            rewound.push(message);
+        }
+    }
+    if !rewound.is_empty() {
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+    for message in rewound {
+        if let Err(e) = source.rewind(message) {
+            tracing::error!(error = e.to_string(), "failed to rewind to message");
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    env,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use lazy_static::lazy_static;
+use reqwest::StatusCode;
+use tokio::sync::Notify;
+use crate::{error::Error, kafka::WORKERS, metrics::DIFF_STORE_CONCURRENCY_LIMIT};
+
+lazy_static! {
+    /// The adaptive limit caps the requests sent by the workers, it can not
+    /// raise concurrency above the number of workers, so the maximum limit is
+    /// at most `WORKERS`.
+    pub static ref DIFF_STORE_CONCURRENCY_CONFIG: Option<AdaptiveLimitConfig> =
+        match env::var("DIFF_STORE_ADAPTIVE_CONCURRENCY").as_deref() {
+            Ok("true") => Some(AdaptiveLimitConfig {
+                initial_limit: env_parse("DIFF_STORE_CONCURRENCY_INITIAL", 4.0),
+                min_limit: env_parse("DIFF_STORE_CONCURRENCY_MIN", 1.0),
+                max_limit: env_parse("DIFF_STORE_CONCURRENCY_MAX", *WORKERS as f64)
+                    .min(*WORKERS as f64),
+                backoff: env_parse("DIFF_STORE_CONCURRENCY_BACKOFF", 0.5),
+                latency_tolerance: env_parse("DIFF_STORE_LATENCY_TOLERANCE", 2.0),
+            }),
+            _ => None,
+        };
+    pub static ref DIFF_STORE_CONCURRENCY: Option<AdaptiveLimiter> =
+        DIFF_STORE_CONCURRENCY_CONFIG.map(AdaptiveLimiter::new);
+}
+
+/// Fails when the adaptive concurrency limit is configured with bounds or a
+/// backoff factor it can not work with.
+pub fn check_adaptive_concurrency() -> Result<(), Error> {
+    match DIFF_STORE_CONCURRENCY_CONFIG.as_ref() {
+        Some(config) => config.validate(),
+        None => Ok(()),
+    }
+}
+
+fn env_parse(key: &str, default: f64) -> f64 {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AdaptiveLimitConfig {
+    pub initial_limit: f64,
+    pub min_limit: f64,
+    pub max_limit: f64,
+    /// Factor the limit is multiplied with when the diff store is overloaded.
+    pub backoff: f64,
+    /// How many times the baseline latency a request may take before it is
+    /// considered a sign of overload.
+    pub latency_tolerance: f64,
+}
+
+impl AdaptiveLimitConfig {
+    /// Fails unless `0 < min_limit <= max_limit` and `0 < backoff < 1`. The
+    /// maximum limit is capped at the number of workers, so a minimum above
+    /// `WORKERS` is invalid.
+    pub fn validate(&self) -> Result<(), Error> {
+        if !(self.min_limit > 0.0 && self.min_limit <= self.max_limit) {
+            return Err(Error::Configuration(format!(
+                "DIFF_STORE_CONCURRENCY_MIN ({}) must be positive and at most the maximum \
+                 concurrency ({}), which is capped at WORKERS",
+                self.min_limit, self.max_limit
+            )));
+        }
+        if !(self.backoff > 0.0 && self.backoff < 1.0) {
+            return Err(Error::Configuration(format!(
+                "DIFF_STORE_CONCURRENCY_BACKOFF ({}) must be between 0 and 1",
+                self.backoff
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Default for AdaptiveLimitConfig {
+    fn default() -> Self {
+        Self {
+            initial_limit: 4.0,
+            min_limit: 1.0,
+            max_limit: 64.0,
+            backoff: 0.5,
+            latency_tolerance: 2.0,
+        }
+    }
+}
+
+/// Outcome of a request, as far as diff store load is concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The request was handled, taking the given time.
+    Success(Duration),
+    /// The diff store rejected the request or did not respond in time.
+    Overload,
+    /// The request failed for a reason unrelated to load.
+    Ignore,
+}
+
+impl Outcome {
+    pub fn from_status(status: StatusCode, latency: Duration) -> Self {
+        match status {
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => Outcome::Overload,
+            // Server errors are often returned without doing the work of the
+            // request, their latency says nothing about the load.
+            status if status.is_server_error() => Outcome::Ignore,
+            _ => Outcome::Success(latency),
+        }
+    }
+}
+
+/// Concurrency limiter adjusting its limit with additive increase and
+/// multiplicative decrease. The limit only caps the concurrency of its
+/// callers, it never adds any. The limit grows by about one per limit's worth of
+/// healthy requests, and is cut when a request is rejected with 429 or 503,
+/// times out, or takes longer than the tolerated multiple of the baseline
+/// latency. The baseline follows the lowest latencies seen, drifting slowly
+/// upwards so that it adapts to a diff store that gets slower overall.
+pub struct AdaptiveLimiter {
+    config: AdaptiveLimitConfig,
+    state: Mutex<LimiterState>,
+    released: Notify,
+}
+
+struct LimiterState {
+    limit: f64,
+    in_flight: usize,
+    baseline: Option<Duration>,
+    last_decrease: Option<Instant>,
+}
+
+/// Permit to send a request, counting as in flight until dropped.
+pub struct AdaptivePermit<'a> {
+    limiter: &'a AdaptiveLimiter,
+}
+
+impl AdaptiveLimiter {
+    pub fn new(config: AdaptiveLimitConfig) -> Self {
+        let limit = config.initial_limit.clamp(config.min_limit, config.max_limit);
+        DIFF_STORE_CONCURRENCY_LIMIT.set(limit as i64);
+        Self {
+            config,
+            state: Mutex::new(LimiterState {
+                limit,
+                in_flight: 0,
+                baseline: None,
+                last_decrease: None,
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.lock().limit as usize
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.lock().in_flight
+    }
+
+    /// Waits until fewer requests than the current limit are in flight.
+    pub async fn acquire(&self) -> AdaptivePermit<'_> {
+        loop {
+            // Register for notification before checking, so that a release
+            // between the check and the wait is not missed.
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+            {
+                let mut state = self.lock();
+                if state.in_flight < (state.limit as usize).max(1) {
+                    state.in_flight += 1;
+                    return AdaptivePermit { limiter: self };
+                }
+            }
+            released.await;
+        }
+    }
+
+    /// Adjusts the limit to the outcome of a request.
+    pub fn record(&self, outcome: Outcome) {
+        let mut state = self.lock();
+        let overloaded = match outcome {
+            Outcome::Success(latency) => {
+                let baseline = match state.baseline {
+                    Some(baseline) if latency >= baseline => {
+                        baseline + (latency - baseline).mul_f64(0.01)
+                    }
+                    _ => latency,
+                };
+                state.baseline = Some(baseline);
+                latency.as_secs_f64() > baseline.as_secs_f64() * self.config.latency_tolerance
+            }
+            Outcome::Overload => true,
+            Outcome::Ignore => return,
+        };
+
+        let limit = if overloaded {
+            // Requests sent before the previous decrease may still report
+            // overload, only decrease once per baseline latency.
+            let cooldown = state.baseline.unwrap_or_default();
+            if state.last_decrease.is_some_and(|last| last.elapsed() < cooldown) {
+                return;
+            }
+            state.last_decrease = Some(Instant::now());
+            state.limit * self.config.backoff
+        } else {
+            state.limit + 1.0 / state.limit
+        };
+        let limit = limit.clamp(self.config.min_limit, self.config.max_limit);
+        if limit as usize != state.limit as usize {
+            tracing::debug!(limit = limit as usize, "adjusted diff store concurrency limit");
+            DIFF_STORE_CONCURRENCY_LIMIT.set(limit as i64);
+        }
+        let increased = limit > state.limit;
+        state.limit = limit;
+        drop(state);
+        if increased {
+            self.released.notify_waiters();
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LimiterState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for AdaptivePermit<'_> {
+    fn drop(&mut self) {
+        self.limiter.lock().in_flight -= 1;
+        self.limiter.released.notify_waiters();
+    }
+}
@@ -1,22 +1,190 @@
-use std::env;
+use std::{
+    env,
+    time::{Duration, Instant, SystemTime},
+};
 use lazy_static::lazy_static;
-use reqwest::StatusCode;
+use oxrdfio::RdfFormat;
+use reqwest::{
+    header::{HeaderValue, RETRY_AFTER},
+    StatusCode,
+};
 use serde::Serialize;
 use crate::{
+    blob_store::{BlobStore, HttpBlobStore, BLOB_STORE_URL},
+    concurrency::{Outcome, DIFF_STORE_CONCURRENCY},
     error::Error,
+    metrics::{
+        DIFF_STORE_BACKOFFS, DIFF_STORE_CONNECTION_ERRORS, DIFF_STORE_OUTCOMES,
+        DIFF_STORE_REQUEST_DURATION, DIFF_STORE_REQUEST_SIZE, DIFF_STORE_RESPONSE_SIZE,
+        DIFF_STORE_TIMEOUTS, GRAPH_SIZE, GRAPH_TRIPLES, OVERSIZED_GRAPHS,
+    },
+    rate_limit::{
+        parse_retry_after, DEFAULT_RETRY_AFTER, DIFF_STORE_BACKOFF, DIFF_STORE_RATE_LIMITER,
+    },
+    rdf::{parse_format, parse_graph, serialize_graph, skolemize},
     schemas::{HarvestEvent, HarvestEventType},
+    telemetry::inject_context,
+    transform::graph_transformations,
+    validation::{apply_policy, report_results, validate, validation_enabled},
 };
 
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
 lazy_static! {
     pub static ref DIFF_STORE_URL: String = env::var("DIFF_STORE_URL").unwrap_or("http://localhost:8090".to_string());
     pub static ref DIFF_STORE_KEY: String = env::var("DIFF_STORE_KEY").unwrap_or("test-key".to_string());
     pub static ref POSTMAN_TYPE: PostmanType = get_postman_type(env::var("POSTMAN_TYPE").unwrap_or("dataset".to_string()));
+    pub static ref DIFF_STORE_CONNECT_TIMEOUT: Duration = env_millis("DIFF_STORE_CONNECT_TIMEOUT_MS", 5_000);
+    pub static ref DIFF_STORE_REQUEST_TIMEOUT: Duration = env_millis("DIFF_STORE_REQUEST_TIMEOUT_MS", 30_000);
+    pub static ref DIFF_STORE_POOL_IDLE_TIMEOUT: Duration = env_millis("DIFF_STORE_POOL_IDLE_TIMEOUT_MS", 90_000);
+    pub static ref DIFF_STORE_POOL_MAX_IDLE_PER_HOST: usize = env::var("DIFF_STORE_POOL_MAX_IDLE_PER_HOST")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(16);
+    pub static ref DIFF_STORE_TCP_KEEPALIVE: Duration = env_millis("DIFF_STORE_TCP_KEEPALIVE_MS", 60_000);
+    pub static ref DIFF_STORE_HTTP2_PRIOR_KNOWLEDGE: bool = env::var("DIFF_STORE_HTTP2_PRIOR_KNOWLEDGE")
+        .map(|value| value == "true")
+        .unwrap_or(false);
+    pub static ref DIFF_STORE_UPSERT_SUCCESS: SuccessStatuses =
+        env_statuses("DIFF_STORE_UPSERT_SUCCESS_STATUSES", "2xx");
+    pub static ref DIFF_STORE_DELETE_SUCCESS: SuccessStatuses =
+        env_statuses("DIFF_STORE_DELETE_SUCCESS_STATUSES", "2xx,404");
+    pub static ref INPUT_GRAPH_FORMAT: RdfFormat = env_format("INPUT_GRAPH_FORMAT").unwrap_or(RdfFormat::Turtle);
+    pub static ref DIFF_STORE_GRAPH_FORMAT: Option<RdfFormat> = env_format("DIFF_STORE_GRAPH_FORMAT");
+    pub static ref SKOLEMIZE_BLANK_NODES: bool = env::var("SKOLEMIZE_BLANK_NODES")
+        .map(|value| value == "true")
+        .unwrap_or(false);
+    pub static ref SKOLEM_BASE_IRI: String = env::var("SKOLEM_BASE_IRI")
+        .unwrap_or("https://data.norge.no/.well-known/genid/".to_string());
+    pub static ref MAX_GRAPH_BYTES: Option<usize> = env::var("MAX_GRAPH_BYTES")
+        .ok()
+        .and_then(|value| value.parse().ok());
+    pub static ref MAX_GRAPH_TRIPLES: Option<usize> = env::var("MAX_GRAPH_TRIPLES")
+        .ok()
+        .and_then(|value| value.parse().ok());
+    pub static ref OVERSIZED_GRAPH_POLICY: OversizedGraphPolicy =
+        match env::var("OVERSIZED_GRAPH_POLICY").unwrap_or("reject".to_string()).as_str() {
+            "claim_check" => OversizedGraphPolicy::ClaimCheck,
+            "reject" => OversizedGraphPolicy::Reject,
+            policy => {
+                tracing::error!(policy, "unknown oversized graph policy, using reject");
+                OversizedGraphPolicy::Reject
+            }
+        };
+}
+
+/// What to do with graphs exceeding `MAX_GRAPH_BYTES` or `MAX_GRAPH_TRIPLES`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OversizedGraphPolicy {
+    /// Fail the event, like any other permanent error.
+    Reject,
+    /// Write the graph to the blob store at `BLOB_STORE_URL` and send a
+    /// reference to it instead. The diff store must be able to read from the
+    /// blob store, so the policy can not be used without one.
+    ClaimCheck,
+}
+
+impl OversizedGraphPolicy {
+    pub fn label(&self) -> &'static str {
+        match self {
+            OversizedGraphPolicy::Reject => "reject",
+            OversizedGraphPolicy::ClaimCheck => "claim_check",
+        }
+    }
+}
+
+fn env_statuses(key: &str, default: &str) -> SuccessStatuses {
+    let spec = env::var(key).unwrap_or(default.to_string());
+    SuccessStatuses::parse(&spec).unwrap_or_else(|e| {
+        tracing::error!(key, spec, error = e.to_string(), "invalid success statuses");
+        std::process::exit(1);
+    })
+}
+
+/// Response statuses accepted as success for a diff store operation,
+/// configured as a comma separated list of status codes and classes, e.g.
+/// `2xx,404`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SuccessStatuses {
+    classes: Vec<u16>,
+    codes: Vec<u16>,
+}
+
+impl SuccessStatuses {
+    pub fn parse(spec: &str) -> Result<Self, Error> {
+        let mut statuses = SuccessStatuses::default();
+        for part in spec.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let invalid = || Error::Configuration(format!("invalid status '{}'", part));
+            match part.strip_suffix("xx").or(part.strip_suffix("XX")) {
+                Some(class) => {
+                    let class: u16 = class.parse().map_err(|_| invalid())?;
+                    if !(1..=5).contains(&class) {
+                        return Err(invalid());
+                    }
+                    statuses.classes.push(class);
+                }
+                None => {
+                    let code = StatusCode::from_bytes(part.as_bytes()).map_err(|_| invalid())?;
+                    statuses.codes.push(code.as_u16());
+                }
+            }
+        }
+        Ok(statuses)
+    }
+
+    pub fn contains(&self, status: StatusCode) -> bool {
+        self.codes.contains(&status.as_u16()) || self.classes.contains(&(status.as_u16() / 100))
+    }
+}
+
+fn env_format(key: &str) -> Option<RdfFormat> {
+    let name = env::var(key).ok().filter(|value| !value.is_empty())?;
+    let format = parse_format(&name);
+    if format.is_none() {
+        tracing::error!(key, name, "unknown rdf format, keeping graphs as they are");
+    }
+    format
+}
+
+/// The format graphs are stored in by the diff store, i.e. the configured
+/// target format, or the input format when graphs are forwarded unchanged.
+pub fn stored_graph_format() -> RdfFormat {
+    DIFF_STORE_GRAPH_FORMAT.unwrap_or(*INPUT_GRAPH_FORMAT)
+}
+
+fn env_millis(key: &str, default: u64) -> Duration {
+    let millis = env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default);
+    Duration::from_millis(millis)
+}
+
+/// Creates the HTTP client used for all diff store requests. The client holds
+/// a connection pool and is meant to be created once and cloned.
+pub fn create_http_client() -> Result<reqwest::Client, Error> {
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(*DIFF_STORE_CONNECT_TIMEOUT)
+        .timeout(*DIFF_STORE_REQUEST_TIMEOUT)
+        .pool_idle_timeout(*DIFF_STORE_POOL_IDLE_TIMEOUT)
+        .pool_max_idle_per_host(*DIFF_STORE_POOL_MAX_IDLE_PER_HOST)
+        .tcp_keepalive(*DIFF_STORE_TCP_KEEPALIVE);
+    if *DIFF_STORE_HTTP2_PRIOR_KNOWLEDGE {
+        builder = builder
+            .http2_prior_knowledge()
+            .http2_keep_alive_interval(*DIFF_STORE_TCP_KEEPALIVE)
+            .http2_keep_alive_while_idle(true);
+    }
+    Ok(builder.build()?)
 }
 
 #[derive(Debug, Serialize)]
 struct DiffStoreGraph {
     pub id: String,
-    pub graph: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub graph: Option<String>,
+    #[serde(rename = "graphRef", skip_serializing_if = "Option::is_none")]
+    pub graph_ref: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -24,7 +192,7 @@ struct DiffStoreID {
     pub id: String,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum PostmanType {
     Dataset,
     DataService,
@@ -32,12 +200,43 @@ pub enum PostmanType {
     Unknown,
 }
 
+impl PostmanType {
+    pub fn reasoned_event_type(&self) -> Option<HarvestEventType> {
+        match self {
+            PostmanType::Dataset => Some(HarvestEventType::DatasetReasoned),
+            PostmanType::DataService => Some(HarvestEventType::DataServiceReasoned),
+            PostmanType::Concept => Some(HarvestEventType::ConceptReasoned),
+            PostmanType::Unknown => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PostmanType::Dataset => "dataset",
+            PostmanType::DataService => "dataservice",
+            PostmanType::Concept => "concept",
+            PostmanType::Unknown => "unknown",
+        }
+    }
+}
+
 pub enum DiffStoreAction {
     PostGraph,
     DeleteGraph,
     Nothing,
 }
 
+impl DiffStoreAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DiffStoreAction::PostGraph => "post",
+            DiffStoreAction::DeleteGraph => "delete",
+            DiffStoreAction::Nothing => "skip",
+        }
+    }
+}
+
+#[tracing::instrument(skip_all, fields(fdk_id = event.fdk_id))]
 pub async fn update_diff_store(
     event: HarvestEvent,
     http_client: &reqwest::Client,
@@ -47,7 +246,7 @@ pub async fn update_diff_store(
             post_event_graph_to_diff_store(event, http_client).await
         }
         DiffStoreAction::DeleteGraph => {
-            delete_graph_in_diff_store(event, http_client).await
+            delete_graph(&event.fdk_id, Some(&idempotency_key(&event)), http_client).await
         }
         DiffStoreAction::Nothing => {
             Ok(())
@@ -55,6 +254,34 @@ pub async fn update_diff_store(
     }
 }
 
+/// Key sent with the diff store writes of an event, so that a write that is
+/// repeated after a crash, before the offset of the event was committed, is
+/// recognized as a duplicate.
+pub fn idempotency_key(event: &HarvestEvent) -> String {
+    format!("{}-{}", event.fdk_id, event.timestamp)
+}
+
+pub fn describe_update(event: &HarvestEvent) -> String {
+    match event_to_action(event.event_type) {
+        DiffStoreAction::PostGraph => format!(
+            "POST {}/api/graphs id={} graph_bytes={}",
+            DIFF_STORE_URL.as_str(),
+            event.fdk_id,
+            event.graph.len()
+        ),
+        DiffStoreAction::DeleteGraph => format!(
+            "DELETE {}/api/graphs id={}",
+            DIFF_STORE_URL.as_str(),
+            event.fdk_id
+        ),
+        DiffStoreAction::Nothing => format!(
+            "SKIP id={} type={:?}",
+            event.fdk_id,
+            event.event_type
+        ),
+    }
+}
+
 fn get_postman_type(postman_type_string: String) -> PostmanType {
     match postman_type_string.as_str() {
         "dataset" => {
@@ -73,7 +300,7 @@ fn get_postman_type(postman_type_string: String) -> PostmanType {
     }
 }
 
-fn event_to_action(event_type: HarvestEventType) -> DiffStoreAction {
+pub fn event_to_action(event_type: HarvestEventType) -> DiffStoreAction {
     match (event_type, POSTMAN_TYPE.clone()) {
         (HarvestEventType::DatasetReasoned, PostmanType::Dataset) => {
             DiffStoreAction::PostGraph
@@ -99,29 +326,82 @@ fn event_to_action(event_type: HarvestEventType) -> DiffStoreAction {
     }
 }
 
-async fn delete_graph_in_diff_store(
-    event: HarvestEvent,
+pub async fn list_graph_ids(http_client: &reqwest::Client) -> Result<Vec<String>, Error> {
+    let request = http_client
+        .get(format!(
+            "{}/api/graphs",
+            DIFF_STORE_URL.clone().as_str()
+        ))
+        .header("X-API-KEY", DIFF_STORE_KEY.clone());
+    let response = send(http_client, request).await?;
+
+    if response.status == StatusCode::OK {
+        serde_json::from_str(&response.body).map_err(|e| Error::Decode(e.to_string()))
+    } else {
+        Err(Error::SinkStatus {
+            message: "Invalid response when listing graphs in diff store".to_string(),
+            status: response.status,
+            body: response.body,
+        })
+    }
+}
+
+pub async fn get_graph(
+    fdk_id: &str,
+    http_client: &reqwest::Client,
+) -> Result<Option<String>, Error> {
+    let request = http_client
+        .get(format!(
+            "{}/api/graphs/{}",
+            DIFF_STORE_URL.clone().as_str(),
+            fdk_id
+        ))
+        .header("X-API-KEY", DIFF_STORE_KEY.clone());
+    let response = send(http_client, request).await?;
+
+    match response.status {
+        StatusCode::OK => Ok(Some(response.body)),
+        StatusCode::NOT_FOUND => Ok(None),
+        status => Err(Error::SinkStatus {
+            message: format!("Invalid response when getting {} from diff store", fdk_id),
+            status,
+            body: response.body,
+        }),
+    }
+}
+
+pub async fn delete_graph_in_diff_store(
+    fdk_id: &str,
+    http_client: &reqwest::Client,
+) -> Result<(), Error> {
+    delete_graph(fdk_id, None, http_client).await
+}
+
+async fn delete_graph(
+    fdk_id: &str,
+    idempotency_key: Option<&str>,
     http_client: &reqwest::Client,
 ) -> Result<(), Error> {
-    let response = http_client
+    let mut request = http_client
         .delete(format!(
             "{}/api/graphs",
             DIFF_STORE_URL.clone().as_str()
         ))
         .header("X-API-KEY", DIFF_STORE_KEY.clone())
-        .json(&DiffStoreID {id: event.fdk_id.clone()})
-        .send()
-        .await?;
+        .json(&DiffStoreID {id: fdk_id.to_string()});
+    if let Some(idempotency_key) = idempotency_key {
+        request = request.header(IDEMPOTENCY_KEY_HEADER, idempotency_key);
+    }
+    let response = send(http_client, request).await?;
 
-    if response.status() == StatusCode::OK {
+    if record_outcome("delete", &DIFF_STORE_DELETE_SUCCESS, response.status) {
         Ok(())
     } else {
-        Err(format!(
-            "Invalid response when deleting {} from diff store: {} - {}",
-            event.fdk_id.as_str(),
-            response.status(),
-            response.text().await?
-        ).into())
+        Err(Error::SinkStatus {
+            message: format!("Invalid response when deleting {} from diff store", fdk_id),
+            status: response.status,
+            body: response.body,
+        })
     }
 }
 
@@ -129,24 +409,272 @@ async fn post_event_graph_to_diff_store(
     event: HarvestEvent,
     http_client: &reqwest::Client,
 ) -> Result<(), Error> {
-    let response = http_client
-        .post(format!(
-            "{}/api/graphs",
-            DIFF_STORE_URL.clone().as_str()
-        ))
+    let key = idempotency_key(&event);
+    // Graphs of too many bytes are not parsed, they are rejected or
+    // claim-checked as received.
+    let prepared = match oversized(event.graph.len(), None) {
+        Some(_) => None,
+        None => prepare_graph(&event).await?,
+    };
+    let (graph, triples, graph_format) = match prepared {
+        Some((graph, triples)) => (graph, Some(triples), stored_graph_format()),
+        None => (event.graph, None, *INPUT_GRAPH_FORMAT),
+    };
+    GRAPH_SIZE
+        .with_label_values(&[POSTMAN_TYPE.label()])
+        .observe(graph.len() as f64);
+    if let Some(triples) = triples {
+        GRAPH_TRIPLES
+            .with_label_values(&[POSTMAN_TYPE.label()])
+            .observe(triples as f64);
+    }
+    let graph = match oversized(graph.len(), triples) {
+        None => GraphContent::Inline(graph),
+        Some(reason) => handle_oversized(&event.fdk_id, &graph, graph_format, reason, http_client).await?,
+    };
+
+    // Graphs converted to `DIFF_STORE_GRAPH_FORMAT` are sent in the `graph`
+    // field like any other graph.
+    let body = match graph {
+        GraphContent::Inline(graph) => DiffStoreGraph {
+            id: event.fdk_id.clone(),
+            graph: Some(graph),
+            graph_ref: None,
+        },
+        GraphContent::Reference(graph_ref) => DiffStoreGraph {
+            id: event.fdk_id.clone(),
+            graph: None,
+            graph_ref: Some(graph_ref),
+        },
+    };
+    let request = http_client
+        .post(format!("{}/api/graphs", DIFF_STORE_URL.clone().as_str()))
         .header("X-API-KEY", DIFF_STORE_KEY.clone())
-        .json(&DiffStoreGraph {id: event.fdk_id.clone(), graph: event.graph})
-        .send()
-        .await?;
+        .header(IDEMPOTENCY_KEY_HEADER, key)
+        .json(&body);
+    let response = send(http_client, request).await?;
 
-    if response.status() == StatusCode::OK {
+    if record_outcome("upsert", &DIFF_STORE_UPSERT_SUCCESS, response.status) {
         Ok(())
     } else {
-        Err(format!(
-            "Invalid response from diff store for {}: {} - {}",
-            event.fdk_id.as_str(),
-            response.status(),
-            response.text().await?
-        ).into())
+        Err(Error::SinkStatus {
+            message: format!("Invalid response from diff store for {}", event.fdk_id),
+            status: response.status,
+            body: response.body,
+        })
+    }
+}
+
+/// The graph sent to the diff store, or a reference to it in the blob store.
+enum GraphContent {
+    Inline(String),
+    Reference(String),
+}
+
+/// Rejects an oversized graph, or writes it to the blob store and returns the
+/// reference to it, depending on the oversized graph policy.
+async fn handle_oversized(
+    fdk_id: &str,
+    graph: &str,
+    format: RdfFormat,
+    reason: String,
+    http_client: &reqwest::Client,
+) -> Result<GraphContent, Error> {
+    let policy = *OVERSIZED_GRAPH_POLICY;
+    OVERSIZED_GRAPHS
+        .with_label_values(&[POSTMAN_TYPE.label(), policy.label()])
+        .inc();
+    match (policy, HttpBlobStore::from_env(http_client)) {
+        (OversizedGraphPolicy::ClaimCheck, Some(blob_store)) => {
+            let graph_ref = blob_store.put(fdk_id, graph.as_bytes(), format.media_type()).await?;
+            tracing::warn!(reason, graph_ref, "graph too large, sending reference to blob store");
+            Ok(GraphContent::Reference(graph_ref))
+        }
+        _ => Err(Error::Validation(format!("graph of {} is too large: {}", fdk_id, reason))),
+    }
+}
+
+/// Fails if the oversized graph policy can not be applied, i.e. claim checks
+/// are configured without a blob store.
+pub fn check_oversized_graph_policy() -> Result<(), Error> {
+    if *OVERSIZED_GRAPH_POLICY == OversizedGraphPolicy::ClaimCheck && BLOB_STORE_URL.is_none() {
+        return Err(Error::Configuration(
+            "oversized graph policy claim_check requires BLOB_STORE_URL".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Counts the outcome of a diff store write, and returns whether it succeeded.
+fn record_outcome(operation: &str, success: &SuccessStatuses, status: StatusCode) -> bool {
+    let succeeded = success.contains(status);
+    let outcome = if succeeded { "success" } else { "failure" };
+    DIFF_STORE_OUTCOMES
+        .with_label_values(&[operation, outcome, status.as_str()])
+        .inc();
+    succeeded
+}
+
+/// Describes why a graph exceeds the configured size limits, if it does.
+fn oversized(bytes: usize, triples: Option<usize>) -> Option<String> {
+    if let Some(max_bytes) = *MAX_GRAPH_BYTES {
+        if bytes > max_bytes {
+            return Some(format!("{} bytes exceeds limit of {}", bytes, max_bytes));
+        }
+    }
+    if let (Some(triples), Some(max_triples)) = (triples, *MAX_GRAPH_TRIPLES) {
+        if triples > max_triples {
+            return Some(format!("{} triples exceeds limit of {}", triples, max_triples));
+        }
+    }
+    None
+}
+
+/// Applies the configured transformations, validation, skolemization and
+/// format conversion to the graph of an event, and returns it with its triple
+/// count. Returns `None` when the graph is to be forwarded unchanged.
+async fn prepare_graph(event: &HarvestEvent) -> Result<Option<(String, usize)>, Error> {
+    if graph_transformations().is_empty()
+        && !validation_enabled()
+        && !*SKOLEMIZE_BLANK_NODES
+        && DIFF_STORE_GRAPH_FORMAT.is_none()
+        && MAX_GRAPH_TRIPLES.is_none()
+    {
+        return Ok(None);
+    }
+
+    let graph = build_graph(event, true).await?;
+    Ok(Some((serialize_graph(&graph, stored_graph_format())?, graph.len())))
+}
+
+/// The graph of an event as it is expected to be stored in the diff store,
+/// built the same way as the posted graph, without reporting validation
+/// results.
+pub async fn graph_to_store(event: &HarvestEvent) -> Result<oxrdf::Graph, Error> {
+    build_graph(event, false).await
+}
+
+/// Parses the graph of an event and applies the configured transformations,
+/// validation policy and skolemization. Validation results are recorded and
+/// published when `report` is set.
+async fn build_graph(event: &HarvestEvent, report: bool) -> Result<oxrdf::Graph, Error> {
+    let parsed = parse_graph(&event.graph, *INPUT_GRAPH_FORMAT)?;
+    let mut graph = graph_transformations().apply(parsed, event)?;
+    if let Some(validation) = validate(&graph) {
+        if report {
+            report_results(&event.fdk_id, &validation).await;
+        }
+        apply_policy(&mut graph, &event.fdk_id, &validation)?;
+    }
+    skolemize_if_enabled(graph, &event.fdk_id)
+}
+
+fn skolemize_if_enabled(graph: oxrdf::Graph, fdk_id: &str) -> Result<oxrdf::Graph, Error> {
+    if *SKOLEMIZE_BLANK_NODES {
+        skolemize(&graph, &SKOLEM_BASE_IRI, fdk_id)
+    } else {
+        Ok(graph)
+    }
+}
+
+/// Pauses all diff store requests for as long as the diff store asked for in
+/// a 429 or 503 response.
+fn back_off(status: StatusCode, retry_after: Option<&HeaderValue>) {
+    let retry_after = retry_after.and_then(|value| value.to_str().ok());
+    let delay = retry_after
+        .and_then(|value| parse_retry_after(value, SystemTime::now()))
+        .unwrap_or(DEFAULT_RETRY_AFTER);
+    let delay = DIFF_STORE_BACKOFF.back_off(delay);
+    DIFF_STORE_BACKOFFS
+        .with_label_values(&[status.as_str()])
+        .inc();
+    tracing::warn!(
+        status = status.as_u16(),
+        retry_after,
+        delay_seconds = delay.as_secs_f64(),
+        "diff store asked to back off, pausing requests"
+    );
+}
+
+struct DiffStoreResponse {
+    status: StatusCode,
+    body: String,
+}
+
+/// Sends a request to the diff store once the rate and concurrency limiters
+/// permit it, and reads the full response, recording request duration, body
+/// sizes and connection failures.
+#[tracing::instrument(name = "diff_store_request", skip_all, fields(otel.kind = "client"))]
+async fn send(
+    http_client: &reqwest::Client,
+    request: reqwest::RequestBuilder,
+) -> Result<DiffStoreResponse, Error> {
+    let mut request = request.build()?;
+    inject_context(request.headers_mut());
+    let method = request.method().to_string();
+    let request_size = request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .map_or(0, |bytes| bytes.len());
+    DIFF_STORE_REQUEST_SIZE
+        .with_label_values(&[&method])
+        .observe(request_size as f64);
+
+    DIFF_STORE_BACKOFF.wait().await;
+    let _permit = DIFF_STORE_RATE_LIMITER.acquire(request_size).await;
+    let _concurrency_permit = match DIFF_STORE_CONCURRENCY.as_ref() {
+        Some(limiter) => Some(limiter.acquire().await),
+        None => None,
+    };
+    let start_time = Instant::now();
+    let result = async {
+        let response = http_client.execute(request).await?;
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE {
+            back_off(status, response.headers().get(RETRY_AFTER));
+        }
+        let body = response.text().await?;
+        Ok::<_, reqwest::Error>(DiffStoreResponse { status, body })
+    }
+    .await;
+    let elapsed_seconds = start_time.elapsed().as_secs_f64();
+
+    if let Some(limiter) = DIFF_STORE_CONCURRENCY.as_ref() {
+        limiter.record(match &result {
+            Ok(response) => Outcome::from_status(response.status, start_time.elapsed()),
+            Err(e) if e.is_timeout() => Outcome::Overload,
+            Err(_) => Outcome::Ignore,
+        });
+    }
+
+    match result {
+        Ok(response) => {
+            DIFF_STORE_REQUEST_DURATION
+                .with_label_values(&[&method, response.status.as_str()])
+                .observe(elapsed_seconds);
+            DIFF_STORE_RESPONSE_SIZE
+                .with_label_values(&[&method])
+                .observe(response.body.len() as f64);
+            Ok(response)
+        }
+        Err(e) => {
+            let status_label = if e.is_timeout() {
+                DIFF_STORE_TIMEOUTS.with_label_values(&[&method]).inc();
+                "timeout"
+            } else if e.is_connect() {
+                DIFF_STORE_CONNECTION_ERRORS.with_label_values(&[&method]).inc();
+                "connection_error"
+            } else {
+                "error"
+            };
+            DIFF_STORE_REQUEST_DURATION
+                .with_label_values(&[&method, status_label])
+                .observe(elapsed_seconds);
+            if e.is_timeout() {
+                Err(Error::Timeout(e.to_string()))
+            } else {
+                Err(e.into())
+            }
+        }
     }
 }
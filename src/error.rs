@@ -1,5 +1,6 @@
 use thiserror::Error;
 use rdkafka::error::KafkaError;
+use reqwest::StatusCode;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -12,11 +13,110 @@ pub enum Error {
     #[error(transparent)]
     ReqwestError(#[from] reqwest::Error),
     #[error(transparent)]
+    RdfSyntaxError(#[from] oxrdfio::RdfSyntaxError),
+    #[error(transparent)]
     SRCError(#[from] schema_registry_converter::error::SRCError),
+    #[error("unable to decode message: {0}")]
+    Decode(String),
+    #[error("unknown schema: {0}")]
+    UnknownSchema(String),
+    #[error("{message}: {status} - {body}")]
+    SinkStatus {
+        message: String,
+        status: StatusCode,
+        body: String,
+    },
+    #[error("timed out: {0}")]
+    Timeout(String),
+    #[error("validation failed: {0}")]
+    Validation(String),
+    #[error("transformation failed: {0}")]
+    Transformation(String),
+    #[error("unable to encode: {0}")]
+    Encode(String),
+    #[error("not found: {0}")]
+    NotFound(String),
+    /// A resource that is expected to become available, e.g. consumer group
+    /// metadata during a rebalance.
+    #[error("unavailable: {0}")]
+    Unavailable(String),
+    #[error("invalid configuration: {0}")]
+    Configuration(String),
     #[error("{0}")]
     String(String),
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Retryability {
+    /// The operation may succeed if attempted again.
+    Retryable,
+    /// The operation will fail again with the same input.
+    Permanent,
+}
+
+impl Error {
+    /// Coarse classification of the error, used as a metric label.
+    pub fn class(&self) -> &'static str {
+        match self {
+            Error::IoError(_) => "io",
+            Error::KafkaError(_) => "kafka",
+            Error::AvroError(_) => "avro",
+            Error::ReqwestError(_) => "network",
+            Error::RdfSyntaxError(_) => "rdf",
+            Error::SRCError(_) | Error::Decode(_) => "decode",
+            Error::UnknownSchema(_) => "unknown_schema",
+            Error::SinkStatus { status, .. } => status_class(*status),
+            Error::Timeout(_) => "timeout",
+            Error::Validation(_) => "validation",
+            Error::Transformation(_) => "transformation",
+            Error::Encode(_) => "encode",
+            Error::NotFound(_) => "not_found",
+            Error::Unavailable(_) => "unavailable",
+            Error::Configuration(_) => "configuration",
+            Error::String(_) => "other",
+        }
+    }
+
+    pub fn retryability(&self) -> Retryability {
+        let retryable = match self {
+            // Fatal transaction errors, e.g. a producer fenced by another
+            // producer with the same transactional id, can not be recovered.
+            Error::KafkaError(KafkaError::Transaction(e)) => !e.is_fatal(),
+            Error::IoError(_)
+            | Error::KafkaError(_)
+            | Error::Timeout(_)
+            | Error::Unavailable(_) => true,
+            Error::ReqwestError(e) => !e.is_builder() && !e.is_decode(),
+            Error::SRCError(e) => e.retriable,
+            Error::SinkStatus { status, .. } => {
+                status.is_server_error()
+                    || *status == StatusCode::REQUEST_TIMEOUT
+                    || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            Error::AvroError(_)
+            | Error::RdfSyntaxError(_)
+            | Error::Decode(_)
+            | Error::UnknownSchema(_)
+            | Error::Validation(_)
+            | Error::Transformation(_)
+            | Error::Encode(_)
+            | Error::NotFound(_)
+            | Error::Configuration(_)
+            | Error::String(_) => false,
+        };
+
+        if retryable {
+            Retryability::Retryable
+        } else {
+            Retryability::Permanent
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.retryability() == Retryability::Retryable
+    }
+}
+
 impl From<&str> for Error {
     fn from(e: &str) -> Self {
         Self::String(e.to_string())
@@ -28,3 +128,25 @@ impl From<String> for Error {
         Self::String(e)
     }
 }
+
+/// Metric label of a sink status. Common statuses get a label of their own,
+/// others are grouped by class, so that the label set stays bounded.
+fn status_class(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        400 => "http_400",
+        401 => "http_401",
+        403 => "http_403",
+        404 => "http_404",
+        409 => "http_409",
+        413 => "http_413",
+        422 => "http_422",
+        429 => "http_429",
+        500 => "http_500",
+        502 => "http_502",
+        503 => "http_503",
+        504 => "http_504",
+        _ if status.is_client_error() => "http_4xx",
+        _ if status.is_server_error() => "http_5xx",
+        _ => "http_other",
+    }
+}
@@ -1,28 +1,44 @@
 use std::{
+    collections::{BTreeMap, BTreeSet},
     env,
+    future::Future,
+    ops::Range,
     time::{Duration, Instant},
 };
 use apache_avro::schema::Name;
 use lazy_static::lazy_static;
 use rdkafka::{
+    bindings,
+    client::NativeClient,
     config::RDKafkaLogLevel,
-    consumer::{Consumer, StreamConsumer},
-    error::KafkaError,
-    message::BorrowedMessage,
-    ClientConfig, Message,
+    consumer::{
+        CommitMode, Consumer, ConsumerContext, DefaultConsumerContext, Rebalance, StreamConsumer,
+    },
+    error::{KafkaError, RDKafkaErrorCode},
+    producer::{FutureProducer, FutureRecord},
+    types::RDKafkaRespErr,
+    util::Timeout,
+    ClientConfig, ClientContext, Message, Offset, Statistics, TopicPartitionList,
 };
+use serde::Deserialize;
+use tracing::Instrument;
 use schema_registry_converter::{
-    async_impl::{
-        avro::AvroDecoder,
-        schema_registry::SrSettings,
-    },
+    async_impl::schema_registry::SrSettings,
     avro_common::DecodeResult,
 };
 use crate::{
+    coalesce::{run_coalescing_processor, COALESCE_WINDOW},
     error::Error,
-    diff_store::update_diff_store,
-    metrics::{PROCESSED_MESSAGES, PROCESSING_TIME},
+    diff_store::{event_to_action, update_diff_store, DiffStoreAction, POSTMAN_TYPE},
+    metrics::{
+        record_consumer_statistics, record_rebalance, ERROR_PROCESSING_TIME, PROCESSED_MESSAGES,
+        PROCESSING_TIME,
+    },
+    output::{AppliedEvent, Output},
+    schema_cache::CachingAvroDecoder,
     schemas::{HarvestEvent, InputEvent},
+    source::{KafkaSource, MessageSource},
+    telemetry::set_parent_from_headers,
 };
 
 lazy_static! {
@@ -31,8 +47,37 @@ lazy_static! {
         env::var("SCHEMA_REGISTRY").unwrap_or("http://localhost:8081".to_string());
     pub static ref INPUT_TOPIC: String =
         env::var("INPUT_TOPIC").unwrap_or("dataset-events".to_string());
+    pub static ref STATISTICS_INTERVAL_MS: String =
+        env::var("STATISTICS_INTERVAL_MS").unwrap_or("10000".to_string());
+    pub static ref COMMIT_MODE: CommitMode = match env::var("COMMIT_MODE").as_deref() {
+        Ok("sync") => CommitMode::Sync,
+        Ok("async") | Err(_) => CommitMode::Async,
+        Ok(mode) => {
+            tracing::error!(mode, "unknown commit mode, using async");
+            CommitMode::Async
+        }
+    };
+    /// Number of workers consuming the input topic. Each worker handles one
+    /// message at a time, so this is also the most requests that can be in
+    /// flight to the diff store.
+    pub static ref WORKERS: usize = env::var("WORKERS")
+        .ok()
+        .and_then(|workers| workers.parse().ok())
+        .filter(|workers| *workers > 0)
+        .unwrap_or(4);
+    pub static ref COMMIT_INTERVAL: Duration = Duration::from_millis(
+        env::var("COMMIT_INTERVAL_MS")
+            .ok()
+            .and_then(|interval| interval.parse().ok())
+            .unwrap_or(5_000)
+    );
 }
 
+pub const CONSUMER_GROUP: &str = "fdk_rdf_postman";
+pub(crate) const KAFKA_TIMEOUT: Duration = Duration::from_secs(10);
+/// Delay before a message that could not be handled is consumed again.
+pub(crate) const RETRY_DELAY: Duration = Duration::from_secs(1);
+
 pub fn create_sr_settings() -> Result<SrSettings, Error> {
     let mut schema_registry_urls = SCHEMA_REGISTRY.split(',');
 
@@ -48,112 +93,590 @@ pub fn create_sr_settings() -> Result<SrSettings, Error> {
     Ok(sr_settings)
 }
 
-pub fn create_consumer() -> Result<StreamConsumer, KafkaError> {
-    let consumer: StreamConsumer = ClientConfig::new()
-        .set("group.id", "fdk_rdf_postman")
+/// Consumer context that exports consumer statistics and rebalances as
+/// metrics, and commits the stored offsets of partitions being revoked.
+pub struct PostmanContext;
+
+impl ClientContext for PostmanContext {
+    fn stats(&self, statistics: Statistics) {
+        record_consumer_statistics(&statistics, CONSUMER_GROUP);
+    }
+}
+
+impl ConsumerContext for PostmanContext {
+    fn rebalance(
+        &self,
+        native_client: &NativeClient,
+        err: RDKafkaRespErr,
+        tpl: &mut TopicPartitionList,
+    ) {
+        if err == RDKafkaRespErr::RD_KAFKA_RESP_ERR__REVOKE_PARTITIONS {
+            commit_revoked_offsets(native_client, tpl);
+        }
+        // The default rebalance of the default context does the assignment,
+        // without calling the callbacks of this context.
+        DefaultConsumerContext.rebalance(native_client, err, tpl);
+        let rebalance = match err {
+            RDKafkaRespErr::RD_KAFKA_RESP_ERR__ASSIGN_PARTITIONS => Rebalance::Assign(tpl),
+            RDKafkaRespErr::RD_KAFKA_RESP_ERR__REVOKE_PARTITIONS => Rebalance::Revoke(tpl),
+            _ => Rebalance::Error(KafkaError::Rebalance(err.into())),
+        };
+        self.post_rebalance(&rebalance);
+    }
+
+    fn post_rebalance(&self, rebalance: &Rebalance<'_>) {
+        record_rebalance(rebalance, CONSUMER_GROUP);
+    }
+}
+
+/// Synchronously commits the offsets stored for processed messages, before
+/// the partitions are revoked and possibly assigned to another consumer.
+fn commit_revoked_offsets(native_client: &NativeClient, revoked: &TopicPartitionList) {
+    // SAFETY: The client pointer is valid for the duration of the rebalance
+    // callback, and a null partition list commits the stored offsets.
+    let err = unsafe { bindings::rd_kafka_commit(native_client.ptr(), std::ptr::null(), 0) };
+    match RDKafkaErrorCode::from(err) {
+        RDKafkaErrorCode::NoError => {
+            tracing::info!(partitions = revoked.count(), "committed offsets of revoked partitions")
+        }
+        // No offsets were stored since the last commit.
+        RDKafkaErrorCode::NoOffset => (),
+        code => tracing::error!(
+            error = code.to_string(),
+            "failed to commit offsets of revoked partitions"
+        ),
+    }
+}
+
+pub type PostmanConsumer = StreamConsumer<PostmanContext>;
+
+pub fn create_consumer() -> Result<PostmanConsumer, KafkaError> {
+    // Offsets are committed by `KafkaSource`, or in transactional mode by the
+    // producer as part of the transaction of each message.
+    let consumer: PostmanConsumer = ClientConfig::new()
+        .set("group.id", CONSUMER_GROUP)
         .set("bootstrap.servers", BROKERS.clone())
         .set("enable.partition.eof", "false")
         .set("session.timeout.ms", "6000")
-        .set("enable.auto.commit", "true")
+        .set("enable.auto.commit", "false")
         .set("enable.auto.offset.store", "false")
+        .set("isolation.level", "read_committed")
         .set("auto.offset.reset", "beginning")
         .set("api.version.request", "false")
+        .set("statistics.interval.ms", STATISTICS_INTERVAL_MS.as_str())
         .set_log_level(RDKafkaLogLevel::Debug)
-        .create()?;
+        .create_with_context(PostmanContext)?;
     consumer.subscribe(&[&INPUT_TOPIC])?;
     Ok(consumer)
 }
 
-pub async fn run_async_processor(worker_id: usize, sr_settings: SrSettings) -> Result<(), Error> {
-    tracing::info!(worker_id, "starting worker");
+pub fn create_producer() -> Result<FutureProducer, KafkaError> {
+    ClientConfig::new()
+        .set("bootstrap.servers", BROKERS.clone())
+        .set("message.timeout.ms", "5000")
+        .create()
+}
 
-    let consumer = create_consumer()?;
-    let mut decoder = AvroDecoder::new(sr_settings);
-    let http_client = reqwest::Client::new();
+/// Produces a message and waits for it to be acknowledged by the broker.
+pub async fn produce(
+    producer: &FutureProducer,
+    topic: &str,
+    key: &str,
+    payload: &[u8],
+) -> Result<(), Error> {
+    producer
+        .send(
+            FutureRecord::to(topic).key(key).payload(payload),
+            Timeout::After(KAFKA_TIMEOUT),
+        )
+        .await
+        .map(|_| ())
+        .map_err(|(e, _)| e.into())
+}
 
-    tracing::info!(worker_id, "listening for messages");
-    loop {
-        let message = consumer.recv().await?;
-        receive_message(&consumer, &mut decoder, &message, &http_client).await;
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayStart {
+    Beginning,
+    Offset(i64),
+    Timestamp(i64),
+}
+
+/// Time without any message or end of partition event after which a scan
+/// checks the positions of the partitions it still reads.
+const SCAN_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn create_replay_consumer() -> Result<StreamConsumer, KafkaError> {
+    ClientConfig::new()
+        .set("group.id", "fdk_rdf_postman_replay")
+        .set("bootstrap.servers", BROKERS.clone())
+        .set("enable.partition.eof", "true")
+        .set("session.timeout.ms", "6000")
+        .set("enable.auto.commit", "false")
+        .set("api.version.request", "false")
+        .set_log_level(RDKafkaLogLevel::Debug)
+        .create()
+}
+
+pub fn topic_partitions(consumer: &StreamConsumer) -> Result<Vec<i32>, Error> {
+    let metadata = consumer.fetch_metadata(Some(&INPUT_TOPIC), KAFKA_TIMEOUT)?;
+    let partitions = metadata
+        .topics()
+        .iter()
+        .find(|topic| topic.name() == INPUT_TOPIC.as_str())
+        .map(|topic| topic.partitions().iter().map(|partition| partition.id()).collect())
+        .unwrap_or_default();
+    Ok(partitions)
+}
+
+/// The partition the default partitioner of the Java Kafka producer assigns
+/// to messages with `key`, given the number of partitions of the topic.
+pub fn key_partition(key: &[u8], partitions: i32) -> i32 {
+    (murmur2(key) & 0x7fffffff) as i32 % partitions
+}
+
+/// The 32-bit murmur2 hash, as implemented by the Java Kafka client.
+fn murmur2(data: &[u8]) -> u32 {
+    const SEED: u32 = 0x9747b28c;
+    const M: u32 = 0x5bd1e995;
+    const R: u32 = 24;
+
+    let mut h = SEED ^ data.len() as u32;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M);
+        h ^= k;
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate() {
+            h ^= (*byte as u32) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
     }
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^= h >> 15;
+    h
 }
 
-async fn receive_message(
+/// Assigns `partitions` of the input topic to `consumer`, positioned at
+/// `start`, and returns the range of offsets to read from each partition. The
+/// end of each range is the high watermark at the time of assignment.
+pub fn assign_partitions(
     consumer: &StreamConsumer,
-    decoder: &mut AvroDecoder<'_>,
-    message: &BorrowedMessage<'_>,
+    partitions: &[i32],
+    start: ReplayStart,
+) -> Result<BTreeMap<i32, Range<i64>>, Error> {
+    let mut ranges = BTreeMap::new();
+    for partition in partitions {
+        let (low, high) = consumer.fetch_watermarks(&INPUT_TOPIC, *partition, KAFKA_TIMEOUT)?;
+        ranges.insert(*partition, low..high);
+    }
+
+    if let ReplayStart::Timestamp(timestamp) = start {
+        let mut timestamps = TopicPartitionList::new();
+        for partition in partitions {
+            timestamps.add_partition_offset(&INPUT_TOPIC, *partition, Offset::Offset(timestamp))?;
+        }
+        for element in consumer.offsets_for_times(timestamps, KAFKA_TIMEOUT)?.elements() {
+            if let Some(range) = ranges.get_mut(&element.partition()) {
+                match element.offset() {
+                    Offset::Offset(offset) => range.start = offset,
+                    // No message at or after the timestamp.
+                    _ => range.start = range.end,
+                }
+            }
+        }
+    }
+
+    let mut assignment = TopicPartitionList::new();
+    for (partition, range) in ranges.iter_mut() {
+        if let ReplayStart::Offset(offset) = start {
+            range.start = offset.clamp(range.start, range.end);
+        }
+        assignment.add_partition_offset(&INPUT_TOPIC, *partition, Offset::Offset(range.start))?;
+    }
+    consumer.assign(&assignment)?;
+    Ok(ranges)
+}
+
+pub trait MessageHandler {
+    /// Called with the offsets that will be read from each partition, before
+    /// the first message is handled.
+    fn assigned(&mut self, _ranges: &BTreeMap<i32, Range<i64>>) {}
+
+    fn handle(
+        &mut self,
+        message: &(impl Message + Sync),
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+/// Reads `partitions` of the input topic, or all of them if `None`, with a
+/// dedicated consumer from `start` up to the high watermark at the time of
+/// assignment, or the end of the partition if reached first, and passes every
+/// message to `handler`. Fails if no partition makes progress for
+/// `SCAN_IDLE_TIMEOUT`.
+pub async fn scan_partitions(
+    partitions: Option<Vec<i32>>,
+    start: ReplayStart,
+    handler: &mut impl MessageHandler,
+) -> Result<(), Error> {
+    let consumer = create_replay_consumer()?;
+    let partitions = match partitions {
+        Some(partitions) => partitions,
+        None => topic_partitions(&consumer)?,
+    };
+    let ranges = assign_partitions(&consumer, &partitions, start)?;
+    handler.assigned(&ranges);
+
+    let mut remaining: BTreeSet<i32> = ranges
+        .iter()
+        .filter(|(_, range)| !range.is_empty())
+        .map(|(partition, _)| *partition)
+        .collect();
+    while !remaining.is_empty() {
+        let message = match tokio::time::timeout(SCAN_IDLE_TIMEOUT, consumer.recv()).await {
+            Ok(Ok(message)) => message,
+            // Transaction markers, aborted transactions and compaction can
+            // leave the offsets before the end of the range unreadable, so a
+            // partition is also done once nothing more can be read from it.
+            Ok(Err(KafkaError::PartitionEOF(partition))) => {
+                remaining.remove(&partition);
+                continue;
+            }
+            Ok(Err(e)) => return Err(e.into()),
+            Err(_) => {
+                let done = scanned_partitions(&consumer, &ranges)?;
+                if !remaining.iter().any(|partition| done.contains(partition)) {
+                    return Err(Error::Timeout(format!(
+                        "no progress scanning partitions {:?} within {:?}",
+                        remaining, SCAN_IDLE_TIMEOUT
+                    )));
+                }
+                remaining.retain(|partition| !done.contains(partition));
+                continue;
+            }
+        };
+        let Some(range) = ranges.get(&message.partition()) else {
+            continue;
+        };
+        if message.offset() >= range.end {
+            remaining.remove(&message.partition());
+            continue;
+        }
+
+        if let Err(e) = handler.handle(&message).await {
+            tracing::warn!(
+                partition = message.partition(),
+                offset = message.offset(),
+                error = e.to_string(),
+                "failed while scanning message"
+            );
+        }
+        if message.offset() + 1 >= range.end {
+            remaining.remove(&message.partition());
+        }
+    }
+    Ok(())
+}
+
+/// The partitions whose consumer position has reached the end of their range.
+fn scanned_partitions(
+    consumer: &StreamConsumer,
+    ranges: &BTreeMap<i32, Range<i64>>,
+) -> Result<BTreeSet<i32>, Error> {
+    Ok(consumer
+        .position()?
+        .elements()
+        .iter()
+        .filter(|element| {
+            let end = ranges.get(&element.partition()).map(|range| range.end);
+            matches!((element.offset(), end), (Offset::Offset(position), Some(end)) if position >= end)
+        })
+        .map(|element| element.partition())
+        .collect())
+}
+
+pub async fn run_async_processor(
+    worker_id: usize,
+    sr_settings: SrSettings,
+    http_client: reqwest::Client,
+) -> Result<(), Error> {
+    tracing::info!(worker_id, "starting worker");
+
+    let source = KafkaSource::create()?;
+    let output = Output::create(worker_id)?;
+    let mut decoder = CachingAvroDecoder::new(sr_settings);
+
+    tracing::info!(worker_id, transactional = output.is_transactional(), "listening for messages");
+    run_processor(worker_id, &source, &mut decoder, &http_client, &output).await
+}
+
+/// Handles messages from `source` until it is exhausted. Messages are
+/// acknowledged once handled successfully or failed permanently, messages
+/// failing with a retryable error are consumed again. Events are coalesced
+/// when `COALESCE_WINDOW_MS` is set.
+pub async fn run_processor<S: MessageSource>(
+    worker_id: usize,
+    source: &S,
+    decoder: &mut CachingAvroDecoder<'_>,
+    http_client: &reqwest::Client,
+    output: &Output,
+) -> Result<(), Error> {
+    if let Some(window) = *COALESCE_WINDOW {
+        if !output.is_transactional() {
+            return run_coalescing_processor(worker_id, source, decoder, http_client, output, window)
+                .await;
+        }
+        tracing::warn!(worker_id, "event coalescing is not supported in transactional mode");
+    }
+
+    while let Some(message) = source.recv().await? {
+        let span = receive_span(worker_id, &message);
+        if let Err(e) = output.begin() {
+            if !e.is_retryable() {
+                return Err(e);
+            }
+            tracing::warn!(parent: &span, error = e.to_string(), "failed to begin transaction");
+            tokio::time::sleep(RETRY_DELAY).await;
+            source.rewind(&message)?;
+            continue;
+        }
+        receive_message(source, decoder, &message, http_client, output)
+            .instrument(span)
+            .await;
+    }
+    Ok(())
+}
+
+pub(crate) fn receive_span(worker_id: usize, message: &(impl Message + Sync)) -> tracing::Span {
+    let span = tracing::info_span!(
+        "receive_message",
+        otel.kind = "consumer",
+        worker_id,
+        topic = message.topic(),
+        partition = message.partition(),
+        offset = message.offset(),
+        schema = tracing::field::Empty,
+        fdk_id = tracing::field::Empty,
+        event_type = tracing::field::Empty,
+    );
+    set_parent_from_headers(&span, message.headers());
+    span
+}
+
+#[derive(Default)]
+struct MessageInfo {
+    event_type: Option<&'static str>,
+    action: Option<&'static str>,
+    fdk_id: Option<String>,
+    timestamp: i64,
+}
+
+async fn receive_message<S: MessageSource>(
+    source: &S,
+    decoder: &mut CachingAvroDecoder<'_>,
+    message: &S::Message<'_>,
     http_client: &reqwest::Client,
+    output: &Output,
 ) {
+    let decoded = decode_message(decoder, message).await;
+    if handle_received(decoded, message, http_client, output).await {
+        acknowledge(source, message, output).await;
+    } else {
+        retry_message(source, message, output).await;
+    }
+}
+
+/// Processes the decoded event of a message and publishes its output record,
+/// recording metrics. Returns whether the message may be acknowledged, i.e. it
+/// was handled successfully, or failed permanently and was dead-lettered or,
+/// without a dead letter topic, skipped.
+pub(crate) async fn handle_received(
+    decoded: Result<InputEvent, Error>,
+    message: &(impl Message + Sync),
+    http_client: &reqwest::Client,
+    output: &Output,
+) -> bool {
     let start_time = Instant::now();
-    let result = handle_message(decoder, message, http_client).await;
+    let mut info = MessageInfo::default();
+    let result = process_event(decoded, http_client, &mut info).await;
     let elapsed_seconds = start_time.elapsed().as_secs_f64();
 
-    let metric_label = match result {
+    let event_type = info.event_type.unwrap_or("unknown");
+    let postman_type = POSTMAN_TYPE.label();
+    // Messages that could not be decoded have no action.
+    let action = info.action.unwrap_or("undecoded");
+    let (status, error_class) = match &result {
         Ok(_) => {
             tracing::info!(elapsed_seconds, "message handled successfully");
-            PROCESSING_TIME.observe(elapsed_seconds);
-            "success"
+            PROCESSING_TIME
+                .with_label_values(&[event_type, postman_type, action])
+                .observe(elapsed_seconds);
+            ("success", "none")
         }
         Err(e) => {
             tracing::error!(
                 elapsed_seconds,
                 error = e.to_string(),
+                error_class = e.class(),
+                retryable = e.is_retryable(),
                 "failed while handling message"
             );
-            "error"
+            let error_class = e.class();
+            ERROR_PROCESSING_TIME
+                .with_label_values(&[event_type, postman_type, action, error_class])
+                .observe(elapsed_seconds);
+            ("error", error_class)
         }
     };
-    PROCESSED_MESSAGES.with_label_values(&[metric_label]).inc();
+    PROCESSED_MESSAGES
+        .with_label_values(&[status, event_type, postman_type, action, error_class])
+        .inc();
 
-    if let Err(e) = consumer.store_offset_from_message(message) {
+    let handled = match (&result, &info.fdk_id) {
+        (Ok(_), Some(fdk_id)) if action != DiffStoreAction::Nothing.label() => {
+            let applied = AppliedEvent {
+                fdk_id: fdk_id.clone(),
+                event_type,
+                timestamp: info.timestamp,
+                action,
+                postman_type,
+            };
+            output.publish_applied(&applied).await.map(|_| true)
+        }
+        (Ok(_), _) => Ok(true),
+        (Err(e), _) if e.is_retryable() => Ok(false),
+        (Err(e), _) => output.publish_dead_letter(message, e).await.map(|dead_lettered| {
+            if !dead_lettered {
+                tracing::warn!("no dead letter topic configured, skipping message");
+            }
+            true
+        }),
+    };
+    handled.unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "failed to publish output record");
+        false
+    })
+}
+
+/// Acknowledges a handled message, committing its transaction in
+/// transactional mode.
+pub(crate) async fn acknowledge<S: MessageSource>(
+    source: &S,
+    message: &S::Message<'_>,
+    output: &Output,
+) {
+    if output.is_transactional() {
+        if let Err(e) = commit_transaction(source, message, output) {
+            tracing::error!(error = e.to_string(), "failed to commit transaction");
+            retry_message(source, message, output).await;
+        }
+    } else if let Err(e) = source.ack(message) {
         tracing::warn!(error = e.to_string(), "failed to store offset");
     };
 }
 
+/// Commits the output records of a message together with its offset.
+fn commit_transaction<S: MessageSource>(
+    source: &S,
+    message: &S::Message<'_>,
+    output: &Output,
+) -> Result<(), Error> {
+    let offsets = source.transaction_offsets(message)?;
+    output.commit(offsets.as_ref())?;
+    match offsets {
+        Some(_) => Ok(()),
+        None => source.ack(message),
+    }
+}
+
+/// Aborts the transaction of a message, if any, and rewinds the source, so
+/// that the message and the messages following it are consumed again.
+async fn retry_message<S: MessageSource>(source: &S, message: &S::Message<'_>, output: &Output) {
+    output.abort();
+    tokio::time::sleep(RETRY_DELAY).await;
+    if let Err(e) = source.rewind(message) {
+        tracing::error!(error = e.to_string(), "failed to rewind to message");
+    }
+}
+
 pub async fn handle_message(
-    decoder: &mut AvroDecoder<'_>,
-    message: &BorrowedMessage<'_>,
+    decoder: &mut CachingAvroDecoder<'_>,
+    message: &(impl Message + Sync),
+    http_client: &reqwest::Client,
+) -> Result<(), Error> {
+    let decoded = decode_message(decoder, message).await;
+    process_event(decoded, http_client, &mut MessageInfo::default()).await
+}
+
+async fn process_event(
+    decoded: Result<InputEvent, Error>,
     http_client: &reqwest::Client,
+    info: &mut MessageInfo,
 ) -> Result<(), Error> {
-    match decode_message(decoder, message).await? {
+    match decoded? {
         InputEvent::HarvestEvent(event) => {
+            tracing::Span::current()
+                .record("fdk_id", event.fdk_id.as_str())
+                .record("event_type", event.event_type.label());
+            info.event_type = Some(event.event_type.label());
+            info.action = Some(event_to_action(event.event_type).label());
+            info.fdk_id = Some(event.fdk_id.clone());
+            info.timestamp = event.timestamp;
             update_diff_store(event, http_client).await
         }
         InputEvent::Unknown { namespace, name } => {
             tracing::warn!(namespace, name, "skipping unknown event");
+            info.action = Some(DiffStoreAction::Nothing.label());
             Ok(())
         }
     }
 }
 
-async fn decode_message(
-    decoder: &mut AvroDecoder<'_>,
-    message: &BorrowedMessage<'_>,
+pub async fn decode_message(
+    decoder: &mut CachingAvroDecoder<'_>,
+    message: &(impl Message + Sync),
 ) -> Result<InputEvent, Error> {
-    match decoder.decode(message.payload()).await? {
-        DecodeResult {
-            name:
-                Some(Name {
-                     name,
-                     namespace: Some(namespace),
-                     ..
-                 }),
-            value,
-        } => {
-            let event = match (namespace.as_str(), name.as_str()) {
-                ("no.fdk.concept", "ConceptEvent") => {
-                    InputEvent::HarvestEvent(apache_avro::from_value::<HarvestEvent>(&value)?)
-                }
-                ("no.fdk.dataservice", "DataServiceEvent") => {
-                    InputEvent::HarvestEvent(apache_avro::from_value::<HarvestEvent>(&value)?)
-                }
-                ("no.fdk.dataset", "DatasetEvent") => {
-                    InputEvent::HarvestEvent(apache_avro::from_value::<HarvestEvent>(&value)?)
-                }
-                _ => InputEvent::Unknown { namespace, name },
-            };
-            Ok(event)
+    // The schema name is recorded on the span of the message being handled,
+    // so that it is part of every subsequent log event for the message.
+    let message_span = tracing::Span::current();
+    async move {
+        match decoder.decode(message.payload()).await? {
+            DecodeResult {
+                name:
+                    Some(Name {
+                         name,
+                         namespace: Some(namespace),
+                         ..
+                     }),
+                value,
+            } => {
+                message_span.record("schema", format!("{}.{}", namespace, name));
+                let event = match (namespace.as_str(), name.as_str()) {
+                    ("no.fdk.concept", "ConceptEvent") => {
+                        InputEvent::HarvestEvent(apache_avro::from_value::<HarvestEvent>(&value)?)
+                    }
+                    ("no.fdk.dataservice", "DataServiceEvent") => {
+                        InputEvent::HarvestEvent(apache_avro::from_value::<HarvestEvent>(&value)?)
+                    }
+                    ("no.fdk.dataset", "DatasetEvent") => {
+                        InputEvent::HarvestEvent(apache_avro::from_value::<HarvestEvent>(&value)?)
+                    }
+                    _ => InputEvent::Unknown { namespace, name },
+                };
+                Ok(event)
+            }
+            _ => Err(Error::UnknownSchema(
+                "unable to identify event without namespace and name".to_string(),
+            )),
         }
-        _ => Err("unable to identify event without namespace and name".into()),
     }
+    .instrument(tracing::info_span!("decode_message"))
+    .await
 }
@@ -1,5 +1,21 @@
+pub mod admin;
+pub mod blob_store;
+pub mod coalesce;
+pub mod concurrency;
+pub mod diff_store;
 pub mod error;
 pub mod kafka;
 pub mod metrics;
+#[cfg(feature = "mock")]
+pub mod mock_diff_store;
+pub mod output;
+pub mod rate_limit;
+pub mod rdf;
+pub mod reconcile;
+pub mod replay;
+pub mod schema_cache;
 pub mod schemas;
-mod diff_store;
+pub mod source;
+pub mod telemetry;
+pub mod transform;
+pub mod validation;
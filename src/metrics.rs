@@ -1,26 +1,233 @@
 use lazy_static::lazy_static;
-use prometheus::{Encoder, Histogram, HistogramOpts, IntCounterVec, Opts, Registry};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+};
 
+use std::collections::HashMap;
+use rdkafka::{consumer::Rebalance, Statistics};
 use crate::error::Error;
 
 lazy_static! {
     pub static ref REGISTRY: Registry = Registry::new();
     pub static ref PROCESSED_MESSAGES: IntCounterVec = IntCounterVec::new(
         Opts::new("processed_messages", "Processed Messages"),
-        &["status"]
+        &["status", "event_type", "postman_type", "action", "error_class"]
     )
     .unwrap_or_else(|e| {
         tracing::error!(error = e.to_string(), "processed_messages metric error");
         std::process::exit(1);
     });
-    pub static ref PROCESSING_TIME: Histogram = Histogram::with_opts(HistogramOpts {
-        common_opts: Opts::new("processing_time", "Event Processing Times"),
-        buckets: vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 100.0],
-    })
+    pub static ref PROCESSING_TIME: HistogramVec = HistogramVec::new(
+        HistogramOpts {
+            common_opts: Opts::new("processing_time", "Event Processing Times"),
+            buckets: vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 100.0],
+        },
+        &["event_type", "postman_type", "action"]
+    )
     .unwrap_or_else(|e| {
         tracing::error!(error = e.to_string(), "processing_time");
         std::process::exit(1);
     });
+    pub static ref ERROR_PROCESSING_TIME: HistogramVec = HistogramVec::new(
+        HistogramOpts {
+            common_opts: Opts::new("error_processing_time", "Failed Event Processing Times"),
+            buckets: vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 100.0],
+        },
+        &["event_type", "postman_type", "action", "error_class"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "error_processing_time");
+        std::process::exit(1);
+    });
+    pub static ref CONSUMER_COMMITTED_OFFSET: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("consumer_committed_offset", "Last Committed Offset per Partition"),
+        &["topic", "partition", "consumer_group"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "consumer_committed_offset metric error");
+        std::process::exit(1);
+    });
+    pub static ref CONSUMER_HIGH_WATERMARK: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("consumer_high_watermark", "High Watermark Offset per Partition"),
+        &["topic", "partition", "consumer_group"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "consumer_high_watermark metric error");
+        std::process::exit(1);
+    });
+    pub static ref CONSUMER_LAG: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("consumer_lag", "Consumer Lag per Partition"),
+        &["topic", "partition", "consumer_group"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "consumer_lag metric error");
+        std::process::exit(1);
+    });
+    pub static ref ASSIGNED_PARTITIONS: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("assigned_partitions", "Assigned Partitions"),
+        &["topic", "consumer_group"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "assigned_partitions metric error");
+        std::process::exit(1);
+    });
+    pub static ref REBALANCES: IntCounterVec = IntCounterVec::new(
+        Opts::new("rebalances", "Consumer Group Rebalances"),
+        &["topic", "consumer_group", "type"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "rebalances metric error");
+        std::process::exit(1);
+    });
+    pub static ref DIFF_STORE_REQUEST_DURATION: HistogramVec = HistogramVec::new(
+        HistogramOpts {
+            common_opts: Opts::new("diff_store_request_duration", "Diff Store Request Durations"),
+            buckets: vec![0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0],
+        },
+        &["method", "status"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "diff_store_request_duration metric error");
+        std::process::exit(1);
+    });
+    pub static ref DIFF_STORE_REQUEST_SIZE: HistogramVec = HistogramVec::new(
+        HistogramOpts {
+            common_opts: Opts::new("diff_store_request_size_bytes", "Diff Store Request Body Sizes"),
+            buckets: prometheus::exponential_buckets(256.0, 4.0, 10).unwrap_or_default(),
+        },
+        &["method"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "diff_store_request_size_bytes metric error");
+        std::process::exit(1);
+    });
+    pub static ref DIFF_STORE_RESPONSE_SIZE: HistogramVec = HistogramVec::new(
+        HistogramOpts {
+            common_opts: Opts::new("diff_store_response_size_bytes", "Diff Store Response Body Sizes"),
+            buckets: prometheus::exponential_buckets(256.0, 4.0, 10).unwrap_or_default(),
+        },
+        &["method"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "diff_store_response_size_bytes metric error");
+        std::process::exit(1);
+    });
+    pub static ref DIFF_STORE_CONNECTION_ERRORS: IntCounterVec = IntCounterVec::new(
+        Opts::new("diff_store_connection_errors", "Diff Store Connection Errors"),
+        &["method"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "diff_store_connection_errors metric error");
+        std::process::exit(1);
+    });
+    pub static ref DIFF_STORE_TIMEOUTS: IntCounterVec = IntCounterVec::new(
+        Opts::new("diff_store_timeouts", "Diff Store Request Timeouts"),
+        &["method"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "diff_store_timeouts metric error");
+        std::process::exit(1);
+    });
+    pub static ref GRAPH_SIZE: HistogramVec = HistogramVec::new(
+        HistogramOpts {
+            common_opts: Opts::new("graph_size_bytes", "Sizes of Graphs Sent to the Diff Store"),
+            buckets: prometheus::exponential_buckets(1024.0, 4.0, 10).unwrap_or_default(),
+        },
+        &["postman_type"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "graph_size_bytes metric error");
+        std::process::exit(1);
+    });
+    pub static ref GRAPH_TRIPLES: HistogramVec = HistogramVec::new(
+        HistogramOpts {
+            common_opts: Opts::new("graph_triples", "Triple Counts of Graphs Sent to the Diff Store"),
+            buckets: prometheus::exponential_buckets(10.0, 4.0, 10).unwrap_or_default(),
+        },
+        &["postman_type"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "graph_triples metric error");
+        std::process::exit(1);
+    });
+    pub static ref DIFF_STORE_THROTTLE_WAIT: HistogramVec = HistogramVec::new(
+        HistogramOpts {
+            common_opts: Opts::new(
+                "diff_store_throttle_wait_seconds",
+                "Time Diff Store Requests Waited for the Rate Limiter"
+            ),
+            buckets: vec![0.0, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0],
+        },
+        &["limit"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "diff_store_throttle_wait_seconds metric error");
+        std::process::exit(1);
+    });
+    pub static ref DIFF_STORE_OUTCOMES: IntCounterVec = IntCounterVec::new(
+        Opts::new("diff_store_outcomes", "Outcomes of Diff Store Writes"),
+        &["operation", "outcome", "status"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "diff_store_outcomes metric error");
+        std::process::exit(1);
+    });
+    pub static ref DIFF_STORE_BACKOFFS: IntCounterVec = IntCounterVec::new(
+        Opts::new("diff_store_backoffs", "Diff Store Requests to Back Off"),
+        &["status"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "diff_store_backoffs metric error");
+        std::process::exit(1);
+    });
+    pub static ref DIFF_STORE_CONCURRENCY_LIMIT: IntGauge = IntGauge::new(
+        "diff_store_concurrency_limit",
+        "Adaptive Concurrency Limit of Diff Store Requests"
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "diff_store_concurrency_limit metric error");
+        std::process::exit(1);
+    });
+    pub static ref COALESCED_EVENTS: IntCounterVec = IntCounterVec::new(
+        Opts::new("coalesced_events", "Events Superseded by a Newer Event for the Same fdkId"),
+        &["postman_type"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "coalesced_events metric error");
+        std::process::exit(1);
+    });
+    pub static ref OVERSIZED_GRAPHS: IntCounterVec = IntCounterVec::new(
+        Opts::new("oversized_graphs", "Graphs Exceeding the Size Limits"),
+        &["postman_type", "policy"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "oversized_graphs metric error");
+        std::process::exit(1);
+    });
+    pub static ref SHACL_VALIDATIONS: IntCounterVec = IntCounterVec::new(
+        Opts::new("shacl_validations", "SHACL Validated Graphs"),
+        &["postman_type", "conforms", "policy"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "shacl_validations metric error");
+        std::process::exit(1);
+    });
+    pub static ref SHACL_VALIDATION_RESULTS: IntCounterVec = IntCounterVec::new(
+        Opts::new("shacl_validation_results", "SHACL Validation Results"),
+        &["postman_type", "severity", "constraint"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "shacl_validation_results metric error");
+        std::process::exit(1);
+    });
+    pub static ref RECONCILE_CHECK_FAILURES: IntCounterVec = IntCounterVec::new(
+        Opts::new("reconcile_check_failures", "Graphs Reconciliation Failed to Compare"),
+        &["postman_type", "error"]
+    )
+    .unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "reconcile_check_failures metric error");
+        std::process::exit(1);
+    });
 }
 
 pub fn register_metrics() {
@@ -37,6 +244,227 @@ pub fn register_metrics() {
             tracing::error!(error = e.to_string(), "processing_time collector error");
             std::process::exit(1);
         });
+
+    REGISTRY
+        .register(Box::new(ERROR_PROCESSING_TIME.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "error_processing_time collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(CONSUMER_COMMITTED_OFFSET.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "consumer_committed_offset collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(CONSUMER_HIGH_WATERMARK.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "consumer_high_watermark collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(CONSUMER_LAG.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "consumer_lag collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(ASSIGNED_PARTITIONS.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "assigned_partitions collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(REBALANCES.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "rebalances collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(DIFF_STORE_REQUEST_DURATION.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "diff_store_request_duration collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(DIFF_STORE_REQUEST_SIZE.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "diff_store_request_size_bytes collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(DIFF_STORE_RESPONSE_SIZE.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "diff_store_response_size_bytes collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(DIFF_STORE_CONNECTION_ERRORS.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "diff_store_connection_errors collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(DIFF_STORE_TIMEOUTS.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "diff_store_timeouts collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(GRAPH_SIZE.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "graph_size_bytes collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(GRAPH_TRIPLES.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "graph_triples collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(DIFF_STORE_THROTTLE_WAIT.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "diff_store_throttle_wait_seconds collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(DIFF_STORE_OUTCOMES.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "diff_store_outcomes collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(DIFF_STORE_BACKOFFS.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "diff_store_backoffs collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(DIFF_STORE_CONCURRENCY_LIMIT.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "diff_store_concurrency_limit collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(COALESCED_EVENTS.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "coalesced_events collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(OVERSIZED_GRAPHS.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "oversized_graphs collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(SHACL_VALIDATIONS.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "shacl_validations collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(SHACL_VALIDATION_RESULTS.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "shacl_validation_results collector error");
+            std::process::exit(1);
+        });
+
+    REGISTRY
+        .register(Box::new(RECONCILE_CHECK_FAILURES.clone()))
+        .unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "reconcile_check_failures collector error");
+            std::process::exit(1);
+        });
+}
+
+pub fn record_consumer_statistics(statistics: &Statistics, consumer_group: &str) {
+    for (topic, topic_statistics) in statistics.topics.iter() {
+        // Partition -1 is librdkafka's internal unassigned partition, and
+        // partitions that are not desired are assigned to other consumers.
+        let partitions = topic_statistics
+            .partitions
+            .values()
+            .filter(|partition| partition.partition >= 0 && partition.desired);
+        for partition in partitions {
+            let partition_label = partition.partition.to_string();
+            let labels = [topic.as_str(), partition_label.as_str(), consumer_group];
+            CONSUMER_COMMITTED_OFFSET
+                .with_label_values(&labels)
+                .set(partition.committed_offset);
+            CONSUMER_HIGH_WATERMARK
+                .with_label_values(&labels)
+                .set(partition.hi_offset);
+            CONSUMER_LAG
+                .with_label_values(&labels)
+                .set(partition.consumer_lag);
+        }
+    }
+}
+
+pub fn record_rebalance(rebalance: &Rebalance, consumer_group: &str) {
+    let (rebalance_type, partitions) = match rebalance {
+        Rebalance::Assign(partitions) => ("assign", partitions.elements()),
+        Rebalance::Revoke(partitions) => ("revoke", partitions.elements()),
+        Rebalance::Error(e) => {
+            tracing::warn!(error = e.to_string(), "rebalance error");
+            REBALANCES
+                .with_label_values(&["", consumer_group, "error"])
+                .inc();
+            return;
+        }
+    };
+
+    let mut partitions_per_topic: HashMap<&str, Vec<i32>> = HashMap::new();
+    for element in partitions.iter() {
+        partitions_per_topic
+            .entry(element.topic())
+            .or_default()
+            .push(element.partition());
+    }
+
+    for (topic, partitions) in partitions_per_topic {
+        REBALANCES
+            .with_label_values(&[topic, consumer_group, rebalance_type])
+            .inc();
+
+        let assigned = ASSIGNED_PARTITIONS.with_label_values(&[topic, consumer_group]);
+        match rebalance_type {
+            "assign" => assigned.add(partitions.len() as i64),
+            _ => {
+                assigned.sub(partitions.len() as i64);
+                for partition in partitions {
+                    let partition_label = partition.to_string();
+                    let labels = [topic, partition_label.as_str(), consumer_group];
+                    let _ = CONSUMER_COMMITTED_OFFSET.remove_label_values(&labels);
+                    let _ = CONSUMER_HIGH_WATERMARK.remove_label_values(&labels);
+                    let _ = CONSUMER_LAG.remove_label_values(&labels);
+                }
+            }
+        }
+    }
 }
 
 pub fn get_metrics() -> Result<String, Error> {
@@ -0,0 +1,383 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    net::TcpListener,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+use actix_web::{dev::ServerHandle, http::StatusCode, web, App, HttpRequest, HttpResponse, HttpServer};
+use serde::{Deserialize, Serialize};
+
+/// In-memory stand-in for the diff store, implementing the `/api/graphs`
+/// endpoints used by the postman, and a `/blobs` blob store for claim-checked
+/// graphs. Requests can be made to fail or be delayed, and what ended up
+/// stored can be inspected.
+pub struct MockDiffStore {
+    url: String,
+    state: Arc<Mutex<MockState>>,
+    handle: ServerHandle,
+}
+
+/// A graph as posted to the diff store.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredGraph {
+    pub id: String,
+    #[serde(default)]
+    pub graph: Option<String>,
+    #[serde(rename = "graphRef", default)]
+    pub graph_ref: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphId {
+    id: String,
+}
+
+/// A request received by the mock, after any injected fault was applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub id: Option<String>,
+    pub idempotency_key: Option<String>,
+    /// The W3C trace context the request was sent with.
+    pub traceparent: Option<String>,
+    pub status: u16,
+}
+
+#[derive(Clone, Debug)]
+struct Fault {
+    /// Only requests for this graph are affected, if set.
+    id: Option<String>,
+    status: Option<StatusCode>,
+    /// Whether the request is still handled, responding with `status`
+    /// instead of the usual one.
+    handle: bool,
+    delay: Duration,
+}
+
+#[derive(Default)]
+struct MockState {
+    api_key: Option<String>,
+    graphs: BTreeMap<String, StoredGraph>,
+    blobs: BTreeMap<String, web::Bytes>,
+    requests: Vec<RecordedRequest>,
+    faults: VecDeque<Fault>,
+    latency: Duration,
+}
+
+impl MockDiffStore {
+    /// Starts the mock on a free port of the loopback interface.
+    pub fn start() -> std::io::Result<Self> {
+        Self::start_on(TcpListener::bind(("127.0.0.1", 0))?)
+    }
+
+    /// Starts the mock on `listener`, it runs until `stop` is called or the
+    /// runtime shuts down.
+    pub fn start_on(listener: TcpListener) -> std::io::Result<Self> {
+        let url = format!("http://{}", listener.local_addr()?);
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let app_state = web::Data::from(state.clone());
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_state.clone())
+                .route("/api/graphs", web::post().to(post_graph))
+                .route("/api/graphs", web::delete().to(delete_graph))
+                .route("/api/graphs", web::get().to(list_graphs))
+                .route("/api/graphs/{id}", web::get().to(get_graph))
+                .route("/blobs/{name}", web::put().to(put_blob))
+                .route("/blobs/{name}", web::get().to(get_blob))
+        })
+        .workers(1)
+        .listen(listener)?
+        .run();
+        let handle = server.handle();
+        tokio::spawn(server);
+        Ok(Self { url, state, handle })
+    }
+
+    /// Base URL of the mock, without a trailing slash.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub async fn stop(&self) {
+        self.handle.stop(true).await;
+    }
+
+    /// Requires requests to have a matching `X-API-KEY` header.
+    pub fn require_api_key(&self, api_key: &str) {
+        self.lock().api_key = Some(api_key.to_string());
+    }
+
+    /// Makes the next `times` requests fail with `status`.
+    pub fn fail_next(&self, status: StatusCode, times: usize) {
+        self.push_faults(None, Some(status), false, Duration::ZERO, times);
+    }
+
+    /// Makes the next `times` requests for the graph `id` fail with `status`,
+    /// leaving requests for other graphs unaffected.
+    pub fn fail_next_for(&self, id: &str, status: StatusCode, times: usize) {
+        self.push_faults(Some(id), Some(status), false, Duration::ZERO, times);
+    }
+
+    /// Handles the next `times` requests for the graph `id` as usual, but
+    /// responds with `status` and no body, e.g. `204 No Content`.
+    pub fn respond_next_for(&self, id: &str, status: StatusCode, times: usize) {
+        self.push_faults(Some(id), Some(status), true, Duration::ZERO, times);
+    }
+
+    /// Delays the response to the next `times` requests, e.g. beyond the
+    /// request timeout of the client.
+    pub fn delay_next(&self, delay: Duration, times: usize) {
+        self.push_faults(None, None, false, delay, times);
+    }
+
+    /// Delays the response to the next `times` requests for the graph `id`,
+    /// leaving requests for other graphs unaffected.
+    pub fn delay_next_for(&self, id: &str, delay: Duration, times: usize) {
+        self.push_faults(Some(id), None, false, delay, times);
+    }
+
+    /// Delays the response to every request.
+    pub fn set_latency(&self, latency: Duration) {
+        self.lock().latency = latency;
+    }
+
+    pub fn insert(&self, id: &str, graph: &str) {
+        self.lock().graphs.insert(
+            id.to_string(),
+            StoredGraph {
+                id: id.to_string(),
+                graph: Some(graph.to_string()),
+                graph_ref: None,
+            },
+        );
+    }
+
+    pub fn graph(&self, id: &str) -> Option<StoredGraph> {
+        self.lock().graphs.get(id).cloned()
+    }
+
+    pub fn graphs(&self) -> BTreeMap<String, StoredGraph> {
+        self.lock().graphs.clone()
+    }
+
+    /// Base URL of the blob store of the mock, for `BLOB_STORE_URL`.
+    pub fn blob_store_url(&self) -> String {
+        format!("{}/blobs", self.url)
+    }
+
+    /// The content of the blob with the given URL.
+    pub fn blob(&self, url: &str) -> Option<Vec<u8>> {
+        let name = url.strip_prefix(&format!("{}/", self.blob_store_url()))?;
+        self.lock().blobs.get(name).map(|blob| blob.to_vec())
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().requests.clone()
+    }
+
+    /// The requests received for the graph `id`.
+    pub fn requests_for(&self, id: &str) -> Vec<RecordedRequest> {
+        self.lock()
+            .requests
+            .iter()
+            .filter(|request| request.id.as_deref() == Some(id))
+            .cloned()
+            .collect()
+    }
+
+    pub fn assert_stored(&self, id: &str, graph: &str) {
+        let stored = self.graph(id);
+        assert_eq!(
+            stored.as_ref().and_then(|stored| stored.graph.as_deref()),
+            Some(graph),
+            "unexpected graph stored for {}",
+            id
+        );
+    }
+
+    pub fn assert_absent(&self, id: &str) {
+        assert_eq!(self.graph(id), None, "graph stored for {}", id);
+    }
+
+    fn push_faults(
+        &self,
+        id: Option<&str>,
+        status: Option<StatusCode>,
+        handle: bool,
+        delay: Duration,
+        times: usize,
+    ) {
+        let mut state = self.lock();
+        for _ in 0..times {
+            state.faults.push_back(Fault {
+                id: id.map(|id| id.to_string()),
+                status,
+                handle,
+                delay,
+            });
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MockState> {
+        lock(&self.state)
+    }
+}
+
+fn lock(state: &Mutex<MockState>) -> MutexGuard<'_, MockState> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// What to do with a request after injected faults were applied.
+enum Intercepted {
+    /// Respond with the status without handling the request.
+    Fail(StatusCode),
+    /// Handle the request, responding with the status if given.
+    Handle(Option<StatusCode>),
+}
+
+/// Applies latency and the next injected fault affecting the graph `id`, and
+/// checks the API key.
+async fn intercept(state: &Mutex<MockState>, request: &HttpRequest, id: Option<&str>) -> Intercepted {
+    let (fault, latency, authorized) = {
+        let mut state = lock(state);
+        let authorized = state.api_key.as_ref().is_none_or(|api_key| {
+            request
+                .headers()
+                .get("X-API-KEY")
+                .is_some_and(|value| value.as_bytes() == api_key.as_bytes())
+        });
+        let fault = state
+            .faults
+            .iter()
+            .position(|fault| fault.id.is_none() || fault.id.as_deref() == id)
+            .and_then(|index| state.faults.remove(index));
+        (fault, state.latency, authorized)
+    };
+
+    let delay = latency + fault.as_ref().map_or(Duration::ZERO, |fault| fault.delay);
+    if !delay.is_zero() {
+        tokio::time::sleep(delay).await;
+    }
+    match fault {
+        Some(Fault { status: Some(status), handle: false, .. }) => Intercepted::Fail(status),
+        _ if !authorized => Intercepted::Fail(StatusCode::UNAUTHORIZED),
+        fault => Intercepted::Handle(fault.and_then(|fault| fault.status)),
+    }
+}
+
+fn record(
+    state: &Mutex<MockState>,
+    request: &HttpRequest,
+    id: Option<&str>,
+    status: StatusCode,
+) -> HttpResponse {
+    lock(state).requests.push(RecordedRequest {
+        method: request.method().to_string(),
+        path: request.path().to_string(),
+        id: id.map(|id| id.to_string()),
+        idempotency_key: request
+            .headers()
+            .get("Idempotency-Key")
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string()),
+        traceparent: request
+            .headers()
+            .get("traceparent")
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string()),
+        status: status.as_u16(),
+    });
+    HttpResponse::build(status).finish()
+}
+
+async fn post_graph(
+    request: HttpRequest,
+    state: web::Data<Mutex<MockState>>,
+    body: web::Json<StoredGraph>,
+) -> HttpResponse {
+    let respond_with = match intercept(&state, &request, Some(&body.id)).await {
+        Intercepted::Fail(status) => return record(&state, &request, Some(&body.id), status),
+        Intercepted::Handle(status) => status,
+    };
+    let graph = body.into_inner();
+    let id = graph.id.clone();
+    lock(&state).graphs.insert(id.clone(), graph);
+    record(&state, &request, Some(&id), respond_with.unwrap_or(StatusCode::OK))
+}
+
+async fn delete_graph(
+    request: HttpRequest,
+    state: web::Data<Mutex<MockState>>,
+    body: web::Json<GraphId>,
+) -> HttpResponse {
+    let respond_with = match intercept(&state, &request, Some(&body.id)).await {
+        Intercepted::Fail(status) => return record(&state, &request, Some(&body.id), status),
+        Intercepted::Handle(status) => status,
+    };
+    let removed = lock(&state).graphs.remove(&body.id);
+    let status = match removed {
+        Some(_) => StatusCode::OK,
+        None => StatusCode::NOT_FOUND,
+    };
+    record(&state, &request, Some(&body.id), respond_with.unwrap_or(status))
+}
+
+async fn list_graphs(request: HttpRequest, state: web::Data<Mutex<MockState>>) -> HttpResponse {
+    if let Intercepted::Fail(status) = intercept(&state, &request, None).await {
+        return record(&state, &request, None, status);
+    }
+    let ids: Vec<String> = lock(&state).graphs.keys().cloned().collect();
+    record(&state, &request, None, StatusCode::OK);
+    HttpResponse::Ok().json(ids)
+}
+
+async fn get_graph(
+    request: HttpRequest,
+    state: web::Data<Mutex<MockState>>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    if let Intercepted::Fail(status) = intercept(&state, &request, Some(&id)).await {
+        return record(&state, &request, Some(&id), status);
+    }
+    let graph = lock(&state).graphs.get(id.as_str()).cloned();
+    match graph.and_then(|graph| graph.graph) {
+        Some(graph) => {
+            record(&state, &request, Some(&id), StatusCode::OK);
+            HttpResponse::Ok().body(graph)
+        }
+        None => record(&state, &request, Some(&id), StatusCode::NOT_FOUND),
+    }
+}
+
+async fn put_blob(
+    request: HttpRequest,
+    state: web::Data<Mutex<MockState>>,
+    name: web::Path<String>,
+    body: web::Bytes,
+) -> HttpResponse {
+    if let Intercepted::Fail(status) = intercept(&state, &request, None).await {
+        return record(&state, &request, None, status);
+    }
+    lock(&state).blobs.insert(name.into_inner(), body);
+    record(&state, &request, None, StatusCode::CREATED)
+}
+
+async fn get_blob(
+    request: HttpRequest,
+    state: web::Data<Mutex<MockState>>,
+    name: web::Path<String>,
+) -> HttpResponse {
+    if let Intercepted::Fail(status) = intercept(&state, &request, None).await {
+        return record(&state, &request, None, status);
+    }
+    let blob = lock(&state).blobs.get(name.as_str()).cloned();
+    match blob {
+        Some(blob) => {
+            record(&state, &request, None, StatusCode::OK);
+            HttpResponse::Ok().body(blob)
+        }
+        None => record(&state, &request, None, StatusCode::NOT_FOUND),
+    }
+}
@@ -0,0 +1,194 @@
+use std::env;
+use lazy_static::lazy_static;
+use rdkafka::{
+    consumer::ConsumerGroupMetadata,
+    message::{Header, OwnedHeaders},
+    producer::{FutureProducer, FutureRecord, Producer},
+    util::Timeout,
+    ClientConfig, Message, TopicPartitionList,
+};
+use serde::Serialize;
+use crate::{
+    diff_store::POSTMAN_TYPE,
+    error::Error,
+    kafka::{BROKERS, KAFKA_TIMEOUT},
+};
+
+lazy_static! {
+    pub static ref APPLIED_EVENTS_TOPIC: Option<String> = env_topic("APPLIED_EVENTS_TOPIC");
+    pub static ref DLQ_TOPIC: Option<String> = env_topic("DLQ_TOPIC");
+    pub static ref KAFKA_TRANSACTIONS: bool = env::var("KAFKA_TRANSACTIONS")
+        .map(|value| value == "true")
+        .unwrap_or(false);
+    /// Prefix of the transactional ids of the workers, which must be unique
+    /// to each instance of the postman, e.g. the name of its pod. Producers
+    /// sharing a transactional id fence each other.
+    pub static ref TRANSACTIONAL_ID_PREFIX: Option<String> =
+        env::var("TRANSACTIONAL_ID_PREFIX").ok().filter(|prefix| !prefix.is_empty());
+}
+
+fn env_topic(key: &str) -> Option<String> {
+    env::var(key).ok().filter(|topic| !topic.is_empty())
+}
+
+/// Fails when transactions are enabled without a transactional id prefix. A
+/// shared default would make every instance fence the producers of the
+/// others.
+pub fn check_transactional_id_prefix() -> Result<(), Error> {
+    if *KAFKA_TRANSACTIONS && TRANSACTIONAL_ID_PREFIX.is_none() {
+        return Err(Error::Configuration(
+            "KAFKA_TRANSACTIONS requires a TRANSACTIONAL_ID_PREFIX unique to this instance"
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// The transactional id of a worker, unique per instance, postman type and
+/// worker.
+pub fn transactional_id(prefix: &str, worker_id: usize) -> String {
+    format!("{}-{}-{}", prefix, POSTMAN_TYPE.label(), worker_id)
+}
+
+/// Record published to the applied events topic for every event applied to
+/// the diff store.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppliedEvent {
+    pub fdk_id: String,
+    #[serde(rename = "type")]
+    pub event_type: &'static str,
+    pub timestamp: i64,
+    pub action: &'static str,
+    pub postman_type: &'static str,
+}
+
+/// Producer of the applied events and dead letter topics. In transactional
+/// mode, the records produced for a message and the consumer offset of the
+/// message are committed in one Kafka transaction, so that a crash can not
+/// publish a record without also committing the offset, or vice versa.
+#[derive(Default)]
+pub struct Output {
+    producer: Option<FutureProducer>,
+    transactional: bool,
+}
+
+impl Output {
+    /// Creates the producer when an output topic or transactions are
+    /// configured. Every worker needs its own transactional id.
+    pub fn create(worker_id: usize) -> Result<Self, Error> {
+        let transactional = *KAFKA_TRANSACTIONS;
+        if !transactional && APPLIED_EVENTS_TOPIC.is_none() && DLQ_TOPIC.is_none() {
+            return Ok(Self::default());
+        }
+
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", BROKERS.clone())
+            .set("enable.idempotence", "true")
+            .set("message.timeout.ms", "10000");
+        if transactional {
+            check_transactional_id_prefix()?;
+            let prefix = TRANSACTIONAL_ID_PREFIX.as_deref().unwrap_or_default();
+            config.set("transactional.id", transactional_id(prefix, worker_id));
+        }
+        let producer: FutureProducer = config.create()?;
+        if transactional {
+            producer.init_transactions(KAFKA_TIMEOUT)?;
+        }
+        Ok(Self {
+            producer: Some(producer),
+            transactional,
+        })
+    }
+
+    pub fn is_transactional(&self) -> bool {
+        self.transactional
+    }
+
+    pub fn begin(&self) -> Result<(), Error> {
+        match &self.producer {
+            Some(producer) if self.transactional => Ok(producer.begin_transaction()?),
+            _ => Ok(()),
+        }
+    }
+
+    /// Commits the open transaction, including the consumer offsets if given.
+    pub fn commit(
+        &self,
+        offsets: Option<&(TopicPartitionList, ConsumerGroupMetadata)>,
+    ) -> Result<(), Error> {
+        match &self.producer {
+            Some(producer) if self.transactional => {
+                if let Some((offsets, group_metadata)) = offsets {
+                    producer.send_offsets_to_transaction(offsets, group_metadata, KAFKA_TIMEOUT)?;
+                }
+                Ok(producer.commit_transaction(KAFKA_TIMEOUT)?)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn abort(&self) {
+        if let Some(producer) = &self.producer {
+            if self.transactional {
+                if let Err(e) = producer.abort_transaction(KAFKA_TIMEOUT) {
+                    tracing::error!(error = e.to_string(), "failed to abort transaction");
+                }
+            }
+        }
+    }
+
+    /// Publishes an applied event record, if an applied events topic is
+    /// configured.
+    pub async fn publish_applied(&self, event: &AppliedEvent) -> Result<(), Error> {
+        let (Some(producer), Some(topic)) = (&self.producer, APPLIED_EVENTS_TOPIC.as_ref()) else {
+            return Ok(());
+        };
+        let payload = serde_json::to_vec(event).map_err(|e| Error::Encode(e.to_string()))?;
+        let record = FutureRecord::to(topic).key(&event.fdk_id).payload(&payload);
+        send(producer, record).await
+    }
+
+    /// Publishes a message that can not be handled to the dead letter topic.
+    /// Returns whether the message was dead-lettered.
+    pub async fn publish_dead_letter(
+        &self,
+        message: &impl Message,
+        error: &Error,
+    ) -> Result<bool, Error> {
+        let (Some(producer), Some(topic)) = (&self.producer, DLQ_TOPIC.as_ref()) else {
+            return Ok(false);
+        };
+        let partition = message.partition().to_string();
+        let offset = message.offset().to_string();
+        let headers = OwnedHeaders::new()
+            .insert(Header { key: "error", value: Some(&error.to_string()) })
+            .insert(Header { key: "error_class", value: Some(error.class()) })
+            .insert(Header { key: "postman_type", value: Some(POSTMAN_TYPE.label()) })
+            .insert(Header { key: "source_topic", value: Some(message.topic()) })
+            .insert(Header { key: "source_partition", value: Some(&partition) })
+            .insert(Header { key: "source_offset", value: Some(&offset) });
+        let mut record: FutureRecord<'_, [u8], [u8]> = FutureRecord::to(topic).headers(headers);
+        if let Some(key) = message.key() {
+            record = record.key(key);
+        }
+        if let Some(payload) = message.payload() {
+            record = record.payload(payload);
+        }
+        send(producer, record).await?;
+        Ok(true)
+    }
+}
+
+async fn send<K, P>(producer: &FutureProducer, record: FutureRecord<'_, K, P>) -> Result<(), Error>
+where
+    K: rdkafka::message::ToBytes + ?Sized,
+    P: rdkafka::message::ToBytes + ?Sized,
+{
+    producer
+        .send(record, Timeout::After(KAFKA_TIMEOUT))
+        .await
+        .map(|_| ())
+        .map_err(|(e, _)| e.into())
+}
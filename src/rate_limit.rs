@@ -0,0 +1,214 @@
+use std::{
+    env,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+use lazy_static::lazy_static;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use crate::metrics::DIFF_STORE_THROTTLE_WAIT;
+
+lazy_static! {
+    pub static ref DIFF_STORE_RATE_LIMITER: RateLimiter = RateLimiter::new(RateLimits {
+        requests_per_second: env_limit("DIFF_STORE_MAX_REQUESTS_PER_SECOND"),
+        bytes_per_second: env_limit("DIFF_STORE_MAX_BYTES_PER_SECOND"),
+        max_in_flight: env_limit("DIFF_STORE_MAX_IN_FLIGHT"),
+    });
+    pub static ref DIFF_STORE_BACKOFF: Backoff = Backoff::new(Duration::from_secs(
+        env_limit("DIFF_STORE_MAX_RETRY_AFTER_SECONDS").unwrap_or(300)
+    ));
+}
+
+/// Delay used when the diff store responds with 429 or 503 without a valid
+/// `Retry-After` header.
+pub const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+fn env_limit<T: std::str::FromStr + PartialOrd + Default>(key: &str) -> Option<T> {
+    env::var(key)
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .filter(|limit| *limit > T::default())
+}
+
+/// Limits on the requests sent to the diff store, shared by all workers.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RateLimits {
+    pub requests_per_second: Option<f64>,
+    pub bytes_per_second: Option<f64>,
+    pub max_in_flight: Option<usize>,
+}
+
+/// Token bucket refilled at `rate` tokens per second, holding at most
+/// `capacity` tokens.
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: f64, capacity: f64) -> Self {
+        Self {
+            rate,
+            capacity,
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes `amount` tokens and returns how long to wait before using them.
+    /// The bucket goes into debt rather than rejecting the reservation, so
+    /// that amounts above the capacity are eventually let through and callers
+    /// are served in the order they reserved.
+    pub fn reserve(&self, amount: f64) -> Duration {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let refill = now.duration_since(state.updated).as_secs_f64() * self.rate;
+        state.tokens = (state.tokens + refill).min(self.capacity) - amount;
+        state.updated = now;
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / self.rate)
+        }
+    }
+}
+
+/// Rate limiter in front of the diff store client. Callers wait for a permit
+/// before sending a request, so workers stop consuming from Kafka while they
+/// are throttled instead of buffering requests.
+pub struct RateLimiter {
+    requests: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+    in_flight: Option<Arc<Semaphore>>,
+}
+
+/// Permit to send a request, counting as in flight until dropped.
+pub struct RatePermit {
+    _in_flight: Option<OwnedSemaphorePermit>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter allowing bursts of one second worth of requests and
+    /// bytes.
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            requests: limits
+                .requests_per_second
+                .map(|rate| TokenBucket::new(rate, rate.max(1.0))),
+            bytes: limits.bytes_per_second.map(|rate| TokenBucket::new(rate, rate)),
+            in_flight: limits
+                .max_in_flight
+                .map(|max_in_flight| Arc::new(Semaphore::new(max_in_flight))),
+        }
+    }
+
+    /// Waits until a request of `bytes` may be sent.
+    pub async fn acquire(&self, bytes: usize) -> RatePermit {
+        // Wait for an in-flight slot first, so that tokens are not reserved
+        // while waiting for it.
+        let in_flight = match &self.in_flight {
+            Some(semaphore) => {
+                let start_time = Instant::now();
+                let permit = semaphore.clone().acquire_owned().await.ok();
+                observe_wait("in_flight", start_time.elapsed());
+                permit
+            }
+            None => None,
+        };
+        let waits = [
+            ("requests", self.requests.as_ref().map(|bucket| bucket.reserve(1.0))),
+            ("bytes", self.bytes.as_ref().map(|bucket| bucket.reserve(bytes as f64))),
+        ];
+        let mut wait = Duration::ZERO;
+        for (limit, limit_wait) in waits {
+            if let Some(limit_wait) = limit_wait {
+                observe_wait(limit, limit_wait);
+                wait = wait.max(limit_wait);
+            }
+        }
+        if !wait.is_zero() {
+            tracing::debug!(wait_seconds = wait.as_secs_f64(), "throttling diff store request");
+            tokio::time::sleep(wait).await;
+        }
+        RatePermit {
+            _in_flight: in_flight,
+        }
+    }
+}
+
+fn observe_wait(limit: &str, wait: Duration) {
+    DIFF_STORE_THROTTLE_WAIT
+        .with_label_values(&[limit])
+        .observe(wait.as_secs_f64());
+}
+
+/// Pause of all diff store requests, requested by the diff store with 429 or
+/// 503 responses.
+pub struct Backoff {
+    max: Duration,
+    until: Mutex<Option<Instant>>,
+}
+
+impl Backoff {
+    /// Creates a backoff that pauses for at most `max` at a time.
+    pub fn new(max: Duration) -> Self {
+        Self {
+            max,
+            until: Mutex::new(None),
+        }
+    }
+
+    /// Pauses requests for `delay`, unless they are already paused for
+    /// longer. Returns the delay applied.
+    pub fn back_off(&self, delay: Duration) -> Duration {
+        let delay = delay.min(self.max);
+        let until = Instant::now() + delay;
+        let mut current = self.until.lock().unwrap_or_else(|e| e.into_inner());
+        if current.is_none_or(|current| current < until) {
+            *current = Some(until);
+        }
+        delay
+    }
+
+    /// Time left until requests may be sent again.
+    pub fn remaining(&self) -> Duration {
+        let until = *self.until.lock().unwrap_or_else(|e| e.into_inner());
+        until.map_or(Duration::ZERO, |until| until.saturating_duration_since(Instant::now()))
+    }
+
+    /// Waits until requests may be sent again, including any extension of
+    /// the pause while waiting.
+    pub async fn wait(&self) {
+        let start_time = Instant::now();
+        loop {
+            let remaining = self.remaining();
+            if remaining.is_zero() {
+                break;
+            }
+            tokio::time::sleep(remaining).await;
+        }
+        let waited = start_time.elapsed();
+        if !waited.is_zero() {
+            observe_wait("retry_after", waited);
+        }
+    }
+}
+
+/// Parses a `Retry-After` header value, either a number of seconds or an
+/// HTTP-date, into the delay from `now`. A date in the past is no delay.
+pub fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()
+            .map(|date| date.duration_since(now).unwrap_or(Duration::ZERO)),
+    }
+}
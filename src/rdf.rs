@@ -0,0 +1,263 @@
+use std::collections::{HashMap, HashSet};
+use oxrdf::{
+    graph::{CanonicalizationAlgorithm, CanonicalizationHashAlgorithm},
+    BlankNode, Dataset, Graph, GraphNameRef, NamedNode, NamedOrBlankNode, Term, Triple,
+    TripleRef,
+};
+use oxrdfio::{RdfFormat, RdfParser, RdfSerializer};
+use sha2::{Digest, Sha256};
+use crate::error::Error;
+
+/// Parses an RDF format from a name such as `turtle`, `n-triples` or
+/// `json-ld`, a file extension or a media type.
+pub fn parse_format(name: &str) -> Option<RdfFormat> {
+    let name = name.trim().to_lowercase();
+    match name.as_str() {
+        "turtle" => Some(RdfFormat::Turtle),
+        "n-triples" | "ntriples" => Some(RdfFormat::NTriples),
+        "json-ld" => RdfFormat::from_extension("jsonld"),
+        "rdf/xml" | "rdf-xml" | "rdfxml" => Some(RdfFormat::RdfXml),
+        _ => RdfFormat::from_extension(&name).or_else(|| RdfFormat::from_media_type(&name)),
+    }
+}
+
+pub fn parse_graph(input: &str, format: RdfFormat) -> Result<Graph, Error> {
+    let mut graph = Graph::new();
+    for quad in RdfParser::from_format(format).for_slice(input) {
+        graph.insert(TripleRef::from(quad?.as_ref()));
+    }
+    Ok(graph)
+}
+
+pub fn serialize_graph(graph: &Graph, format: RdfFormat) -> Result<String, Error> {
+    let mut serializer = RdfSerializer::from_format(format).for_writer(Vec::new());
+    for triple in graph.iter() {
+        serializer.serialize_triple(triple)?;
+    }
+    let bytes = serializer.finish()?;
+    String::from_utf8(bytes).map_err(|e| Error::Decode(e.to_string()))
+}
+
+/// Converts a serialized graph from one RDF format to another.
+pub fn convert_graph(input: &str, from: RdfFormat, to: RdfFormat) -> Result<String, Error> {
+    serialize_graph(&parse_graph(input, from)?, to)
+}
+
+/// Replaces blank nodes with skolem IRIs below `base_iri`. The IRI of a blank
+/// node is derived from `fdk_id` and the hash of the node's own triples, as in
+/// the first degree hash of RDFC-1.0, so it does not depend on the labels of
+/// the input nor on blank nodes elsewhere in the graph. Adding or removing a
+/// blank node only changes the IRIs of nodes whose triples change.
+pub fn skolemize(graph: &Graph, base_iri: &str, fdk_id: &str) -> Result<Graph, Error> {
+    let hashes = blank_node_hashes(graph);
+    let mut skolem_iris: HashMap<BlankNode, NamedNode> = HashMap::new();
+    for (node, hash) in hashes {
+        let hash = Sha256::new()
+            .chain_update(fdk_id.as_bytes())
+            .chain_update([0])
+            .chain_update(hash.as_bytes())
+            .finalize();
+        let iri = NamedNode::new(format!("{}{:x}", base_iri, hash))
+            .map_err(|e| Error::Transformation(format!("invalid skolem iri: {}", e)))?;
+        skolem_iris.insert(node, iri);
+    }
+
+    let mut skolemized = Graph::new();
+    for triple in graph.iter() {
+        let triple = triple.into_owned();
+        let subject = match triple.subject {
+            NamedOrBlankNode::BlankNode(node) => skolem_iris[&node].clone().into(),
+            subject => subject,
+        };
+        let object = match triple.object {
+            Term::BlankNode(node) => skolem_iris[&node].clone().into(),
+            object => object,
+        };
+        skolemized.insert(&Triple::new(subject, triple.predicate, object));
+    }
+    Ok(skolemized)
+}
+
+/// A hash per blank node of `graph` that identifies it by its own triples.
+/// Nodes with the same first degree hash are told apart by refining their
+/// hashes with those of their neighbours, within the blank nodes connected to
+/// them only. Nodes that still can not be told apart, i.e. that are
+/// interchangeable, are told apart by the order of their RDFC-1.0 labels.
+fn blank_node_hashes(graph: &Graph) -> HashMap<BlankNode, String> {
+    let mut triples: HashMap<BlankNode, Vec<Triple>> = HashMap::new();
+    for triple in graph.iter() {
+        let triple = triple.into_owned();
+        if let NamedOrBlankNode::BlankNode(node) = &triple.subject {
+            triples.entry(node.clone()).or_default().push(triple.clone());
+        }
+        if let Term::BlankNode(node) = &triple.object {
+            if triple.subject != NamedOrBlankNode::BlankNode(node.clone()) {
+                triples.entry(node.clone()).or_default().push(triple.clone());
+            }
+        }
+    }
+
+    let first_degree: HashMap<BlankNode, String> = triples
+        .iter()
+        .map(|(node, triples)| (node.clone(), first_degree_hash(node, triples)))
+        .collect();
+    let mut hashes = first_degree.clone();
+    for component in connected_blank_nodes(&triples) {
+        if tied(&component, &first_degree).is_empty() {
+            continue;
+        }
+        // The partition is stable after as many rounds as there are nodes.
+        let mut refined = first_degree.clone();
+        for _ in 0..component.len() {
+            refined = component
+                .iter()
+                .map(|node| {
+                    let hash = neighbour_hash(node, &triples[node], &first_degree, &refined);
+                    (node.clone(), hash)
+                })
+                .collect();
+        }
+        for node in tied(&component, &first_degree) {
+            hashes.insert(node.clone(), refined[&node].clone());
+        }
+    }
+
+    let mut ties: HashMap<String, Vec<BlankNode>> = HashMap::new();
+    let all: Vec<BlankNode> = hashes.keys().cloned().collect();
+    for node in tied(&all, &hashes) {
+        ties.entry(hashes[&node].clone()).or_default().push(node);
+    }
+    if !ties.is_empty() {
+        let mut dataset = Dataset::new();
+        dataset.extend(graph.iter().map(|triple| triple.in_graph(GraphNameRef::DefaultGraph)));
+        let labels = dataset.canonicalize_blank_nodes(CanonicalizationAlgorithm::Rdfc10 {
+            hash_algorithm: CanonicalizationHashAlgorithm::Sha256,
+        });
+        let label = |node: &BlankNode| {
+            labels[&node.as_ref()].as_str()[4..].parse::<usize>().unwrap_or_default()
+        };
+        for (hash, mut nodes) in ties {
+            nodes.sort_by_key(label);
+            for (index, node) in nodes.into_iter().enumerate() {
+                hashes.insert(node, format!("{}-{}", hash, index));
+            }
+        }
+    }
+    hashes
+}
+
+/// The blank nodes of each set of blank nodes connected by triples.
+fn connected_blank_nodes(triples: &HashMap<BlankNode, Vec<Triple>>) -> Vec<Vec<BlankNode>> {
+    let mut components = Vec::new();
+    let mut seen: HashSet<&BlankNode> = HashSet::new();
+    for start in triples.keys() {
+        if !seen.insert(start) {
+            continue;
+        }
+        let mut component = Vec::new();
+        let mut queue = vec![start];
+        while let Some(node) = queue.pop() {
+            component.push(node.clone());
+            for triple in &triples[node] {
+                let neighbours = [
+                    match &triple.subject {
+                        NamedOrBlankNode::BlankNode(other) => Some(other),
+                        _ => None,
+                    },
+                    match &triple.object {
+                        Term::BlankNode(other) => Some(other),
+                        _ => None,
+                    },
+                ];
+                for other in neighbours.into_iter().flatten() {
+                    if let Some((other, _)) = triples.get_key_value(other) {
+                        if seen.insert(other) {
+                            queue.push(other);
+                        }
+                    }
+                }
+            }
+        }
+        components.push(component);
+    }
+    components
+}
+
+/// The RDFC-1.0 first degree hash of `node`: the hash of its sorted triples,
+/// with `node` labelled `a` and every other blank node `z`.
+fn first_degree_hash(node: &BlankNode, triples: &[Triple]) -> String {
+    let relabel = |other: &BlankNode| {
+        BlankNode::new_unchecked(if other == node { "a" } else { "z" })
+    };
+    let mut lines: Vec<String> = triples
+        .iter()
+        .map(|triple| {
+            let subject = match &triple.subject {
+                NamedOrBlankNode::BlankNode(other) => relabel(other).into(),
+                subject => subject.clone(),
+            };
+            let object = match &triple.object {
+                Term::BlankNode(other) => relabel(other).into(),
+                object => object.clone(),
+            };
+            format!("{} .\n", Triple::new(subject, triple.predicate.clone(), object))
+        })
+        .collect();
+    lines.sort();
+    format!("{:x}", Sha256::digest(lines.concat()))
+}
+
+/// The first degree hash of `node` combined with the current hashes of the
+/// blank nodes it shares a triple with, each with the predicate and direction
+/// of the triple.
+fn neighbour_hash(
+    node: &BlankNode,
+    triples: &[Triple],
+    first_degree: &HashMap<BlankNode, String>,
+    hashes: &HashMap<BlankNode, String>,
+) -> String {
+    let mut neighbours: Vec<String> = triples
+        .iter()
+        .filter_map(|triple| match (&triple.subject, &triple.object) {
+            (NamedOrBlankNode::BlankNode(subject), Term::BlankNode(object)) if subject == node => {
+                Some(format!("o {} {}", triple.predicate, hashes[object]))
+            }
+            (NamedOrBlankNode::BlankNode(subject), _) if subject != node => {
+                Some(format!("s {} {}", triple.predicate, hashes[subject]))
+            }
+            _ => None,
+        })
+        .collect();
+    neighbours.sort();
+    let mut hash = Sha256::new().chain_update(first_degree[node].as_bytes());
+    for neighbour in neighbours {
+        hash.update(neighbour.as_bytes());
+    }
+    format!("{:x}", hash.finalize())
+}
+
+/// The nodes of `nodes` whose hash is shared with another node of `nodes`.
+fn tied(nodes: &[BlankNode], hashes: &HashMap<BlankNode, String>) -> Vec<BlankNode> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for node in nodes {
+        *counts.entry(hashes[node].as_str()).or_default() += 1;
+    }
+    nodes
+        .iter()
+        .filter(|node| counts[hashes[*node].as_str()] > 1)
+        .cloned()
+        .collect()
+}
+
+/// Whether two graphs are equal up to blank node labels.
+pub fn isomorphic(a: &Graph, b: &Graph) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut a = a.clone();
+    let mut b = b.clone();
+    a.canonicalize(CanonicalizationAlgorithm::Unstable);
+    b.canonicalize(CanonicalizationAlgorithm::Unstable);
+    a == b
+}
@@ -0,0 +1,292 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    future::Future,
+};
+use rdkafka::{
+    consumer::{Consumer, StreamConsumer},
+    error::KafkaError,
+    Message, Offset, TopicPartitionList,
+};
+use serde::Serialize;
+use crate::{
+    diff_store::{
+        delete_graph_in_diff_store, event_to_action, get_graph, list_graph_ids,
+        graph_to_store, stored_graph_format, update_diff_store, DiffStoreAction, POSTMAN_TYPE,
+    },
+    error::Error,
+    kafka::{
+        create_replay_consumer, create_sr_settings, decode_message, scan_partitions,
+        MessageHandler, ReplayStart, INPUT_TOPIC, KAFKA_TIMEOUT,
+    },
+    metrics::RECONCILE_CHECK_FAILURES,
+    rdf::{isomorphic, parse_graph},
+    schema_cache::CachingAvroDecoder,
+    schemas::{HarvestEvent, HarvestEventType, InputEvent},
+};
+
+/// Position and type of the newest event of an fdkId. The graph of the event
+/// is read again from the topic when needed, so that a scan of the whole
+/// topic does not hold every graph in memory.
+#[derive(Clone, Copy, Debug)]
+pub struct LatestEvent {
+    pub partition: i32,
+    pub offset: i64,
+    pub timestamp: i64,
+    pub event_type: HarvestEventType,
+}
+
+/// Keeps the newest event per fdkId, ignoring events this postman takes no
+/// action on.
+pub struct LatestEvents<'a> {
+    decoder: CachingAvroDecoder<'a>,
+    fdk_id: Option<String>,
+    pub events: HashMap<String, LatestEvent>,
+}
+
+impl<'a> LatestEvents<'a> {
+    pub fn new(fdk_id: Option<String>) -> Result<Self, Error> {
+        Ok(Self::with_decoder(CachingAvroDecoder::new(create_sr_settings()?), fdk_id))
+    }
+
+    pub fn with_decoder(decoder: CachingAvroDecoder<'a>, fdk_id: Option<String>) -> Self {
+        Self {
+            decoder,
+            fdk_id,
+            events: HashMap::new(),
+        }
+    }
+}
+
+impl MessageHandler for LatestEvents<'_> {
+    async fn handle(&mut self, message: &(impl Message + Sync)) -> Result<(), Error> {
+        let InputEvent::HarvestEvent(event) = decode_message(&mut self.decoder, message).await?
+        else {
+            return Ok(());
+        };
+        if self.fdk_id.as_ref().is_some_and(|fdk_id| *fdk_id != event.fdk_id) {
+            return Ok(());
+        }
+        if let DiffStoreAction::Nothing = event_to_action(event.event_type) {
+            return Ok(());
+        }
+
+        let newer = self
+            .events
+            .get(&event.fdk_id)
+            .is_none_or(|latest| event.timestamp >= latest.timestamp);
+        if newer {
+            let latest = LatestEvent {
+                partition: message.partition(),
+                offset: message.offset(),
+                timestamp: event.timestamp,
+                event_type: event.event_type,
+            };
+            self.events.insert(event.fdk_id, latest);
+        }
+        Ok(())
+    }
+}
+
+/// Reads single events of the input topic by position.
+pub trait EventReader {
+    fn read(
+        &mut self,
+        partition: i32,
+        offset: i64,
+    ) -> impl Future<Output = Result<HarvestEvent, Error>> + Send;
+}
+
+/// Reads events from the input topic with a dedicated consumer.
+pub struct KafkaEventReader<'a> {
+    consumer: StreamConsumer,
+    decoder: CachingAvroDecoder<'a>,
+}
+
+impl KafkaEventReader<'_> {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            consumer: create_replay_consumer()?,
+            decoder: CachingAvroDecoder::new(create_sr_settings()?),
+        })
+    }
+}
+
+impl EventReader for KafkaEventReader<'_> {
+    async fn read(&mut self, partition: i32, offset: i64) -> Result<HarvestEvent, Error> {
+        let mut assignment = TopicPartitionList::new();
+        assignment.add_partition_offset(&INPUT_TOPIC, partition, Offset::Offset(offset))?;
+        self.consumer.assign(&assignment)?;
+
+        let not_found = || {
+            Error::NotFound(format!("event at offset {} of partition {}", offset, partition))
+        };
+        loop {
+            let message = match tokio::time::timeout(KAFKA_TIMEOUT, self.consumer.recv()).await {
+                Ok(Ok(message)) => message,
+                Ok(Err(KafkaError::PartitionEOF(eof_partition))) if eof_partition == partition => {
+                    return Err(not_found());
+                }
+                Ok(Err(KafkaError::PartitionEOF(_))) => continue,
+                Ok(Err(e)) => return Err(e.into()),
+                Err(_) => {
+                    return Err(Error::Timeout(format!(
+                        "reading offset {} of partition {}",
+                        offset, partition
+                    )))
+                }
+            };
+            // Skip anything still buffered from a previous assignment.
+            if message.partition() != partition || message.offset() < offset {
+                continue;
+            }
+            if message.offset() > offset {
+                return Err(not_found());
+            }
+            return match decode_message(&mut self.decoder, &message).await? {
+                InputEvent::HarvestEvent(event) => Ok(event),
+                InputEvent::Unknown { namespace, name } => Err(Error::UnknownSchema(format!(
+                    "{}.{} at offset {} of partition {}",
+                    namespace, name, offset, partition
+                ))),
+            };
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReconcileReport {
+    /// Graphs whose latest event is a reasoned event, but are absent from the
+    /// diff store.
+    pub missing: BTreeSet<String>,
+    /// Graphs in the diff store that are removed, or unknown, on the topic.
+    pub orphaned: BTreeSet<String>,
+    /// Graphs in the diff store that differ from the latest event.
+    pub stale: BTreeSet<String>,
+    /// Graphs that could not be compared, because either the latest event or
+    /// the stored graph could not be read.
+    pub unchecked: BTreeSet<String>,
+    pub repaired: usize,
+    pub repair_failures: usize,
+}
+
+/// Compares the latest state of every fdkId on the input topic with the
+/// content of the diff store, optionally repairing any differences.
+pub async fn reconcile(
+    repair: bool,
+    http_client: &reqwest::Client,
+) -> Result<ReconcileReport, Error> {
+    let mut latest = LatestEvents::new(None)?;
+    scan_partitions(None, ReplayStart::Beginning, &mut latest).await?;
+    let mut reader = KafkaEventReader::new()?;
+    reconcile_events(&latest.events, &mut reader, repair, http_client).await
+}
+
+/// Compares the latest events of each fdkId with the content of the diff
+/// store. Graphs that can not be compared are reported as unchecked, without
+/// ending the reconciliation.
+pub async fn reconcile_events(
+    latest: &HashMap<String, LatestEvent>,
+    reader: &mut impl EventReader,
+    repair: bool,
+    http_client: &reqwest::Client,
+) -> Result<ReconcileReport, Error> {
+    let stored = list_graph_ids(http_client)
+        .await?
+        .into_iter()
+        .collect::<BTreeSet<String>>();
+    tracing::info!(
+        topic_ids = latest.len(),
+        diff_store_ids = stored.len(),
+        "reconciling topic with diff store"
+    );
+
+    let mut report = ReconcileReport::default();
+    for (fdk_id, event) in latest.iter() {
+        match event_to_action(event.event_type) {
+            DiffStoreAction::PostGraph if !stored.contains(fdk_id) => {
+                report.missing.insert(fdk_id.clone());
+            }
+            DiffStoreAction::PostGraph => match is_stale(event, reader, http_client).await {
+                Ok(true) => {
+                    report.stale.insert(fdk_id.clone());
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    tracing::error!(fdk_id, error = e.to_string(), "unable to compare graph");
+                    RECONCILE_CHECK_FAILURES
+                        .with_label_values(&[POSTMAN_TYPE.label(), e.class()])
+                        .inc();
+                    report.unchecked.insert(fdk_id.clone());
+                }
+            },
+            DiffStoreAction::DeleteGraph if stored.contains(fdk_id) => {
+                report.orphaned.insert(fdk_id.clone());
+            }
+            _ => {}
+        }
+    }
+    report.orphaned.extend(
+        stored
+            .into_iter()
+            .filter(|fdk_id| !latest.contains_key(fdk_id)),
+    );
+    tracing::info!(
+        missing = report.missing.len(),
+        orphaned = report.orphaned.len(),
+        stale = report.stale.len(),
+        unchecked = report.unchecked.len(),
+        "reconciliation finished"
+    );
+
+    if repair {
+        for fdk_id in report.missing.iter().chain(report.stale.iter()) {
+            if let Some(event) = latest.get(fdk_id) {
+                let result = match reader.read(event.partition, event.offset).await {
+                    Ok(event) => update_diff_store(event, http_client).await,
+                    Err(e) => Err(e),
+                };
+                count_repair(&mut report.repaired, &mut report.repair_failures, fdk_id, result);
+            }
+        }
+        for fdk_id in report.orphaned.iter() {
+            let result = delete_graph_in_diff_store(fdk_id, http_client).await;
+            count_repair(&mut report.repaired, &mut report.repair_failures, fdk_id, result);
+        }
+    }
+    Ok(report)
+}
+
+async fn is_stale(
+    latest: &LatestEvent,
+    reader: &mut impl EventReader,
+    http_client: &reqwest::Client,
+) -> Result<bool, Error> {
+    let event = reader.read(latest.partition, latest.offset).await?;
+    let Some(stored) = get_graph(&event.fdk_id, http_client).await? else {
+        return Ok(true);
+    };
+
+    let expected = graph_to_store(&event).await;
+    let actual = parse_graph(&stored, stored_graph_format());
+    match (expected, actual) {
+        (Ok(expected), Ok(actual)) => Ok(!isomorphic(&expected, &actual)),
+        // Fall back to comparing the serializations when either can not be
+        // parsed.
+        _ => Ok(event.graph.trim() != stored.trim()),
+    }
+}
+
+fn count_repair(
+    repaired: &mut usize,
+    failures: &mut usize,
+    fdk_id: &str,
+    result: Result<(), Error>,
+) {
+    match result {
+        Ok(_) => *repaired += 1,
+        Err(e) => {
+            tracing::error!(fdk_id, error = e.to_string(), "failed to repair graph");
+            *failures += 1;
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use crate::{
+    diff_store::{describe_update, update_diff_store, POSTMAN_TYPE},
+    error::Error,
+    schemas::HarvestEvent,
+};
+
+#[derive(Debug, Default)]
+pub struct ReplaySummary {
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+/// Events read from a replay input, one at a time.
+pub type Events = Box<dyn Iterator<Item = Result<HarvestEvent, Error>>>;
+
+/// Reads events from `path` and runs them through the same action mapping and
+/// diff store calls as events consumed from Kafka. With `dry_run` set, the
+/// intended requests are printed instead of sent. Events are read one at a
+/// time, so that only one graph is held in memory. Events that can not be
+/// read are counted as failed.
+pub async fn replay(
+    path: &Path,
+    dry_run: bool,
+    http_client: &reqwest::Client,
+) -> Result<ReplaySummary, Error> {
+    let events = read_events(path)?;
+    tracing::info!(path = path.display().to_string(), dry_run, "replaying events");
+
+    let mut summary = ReplaySummary::default();
+    for event in events {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                tracing::error!(error = e.to_string(), "failed to read event");
+                summary.failed += 1;
+                continue;
+            }
+        };
+        if dry_run {
+            println!("{}", describe_update(&event));
+            summary.succeeded += 1;
+            continue;
+        }
+
+        let fdk_id = event.fdk_id.clone();
+        match update_diff_store(event, http_client).await {
+            Ok(_) => summary.succeeded += 1,
+            Err(e) => {
+                tracing::error!(fdk_id, error = e.to_string(), "failed while replaying event");
+                summary.failed += 1;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Reads events from a JSONL file, an Avro object container file, or a
+/// directory of Turtle files where each file name is the fdkId of the graph.
+pub fn read_events(path: &Path) -> Result<Events, Error> {
+    if path.is_dir() {
+        return read_turtle_dir(path);
+    }
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("jsonl") | Some("json") => read_jsonl(path),
+        Some("avro") => read_avro(path),
+        _ => Err(Error::Configuration(format!("unsupported replay file: {}", path.display()))),
+    }
+}
+
+fn read_jsonl(path: &Path) -> Result<Events, Error> {
+    let lines = BufReader::new(File::open(path)?)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|(i, line)| {
+            serde_json::from_str::<HarvestEvent>(&line?)
+                .map_err(|e| Error::Decode(format!("invalid event on line {}: {}", i + 1, e)))
+        });
+    Ok(Box::new(lines))
+}
+
+fn read_avro(path: &Path) -> Result<Events, Error> {
+    let values = apache_avro::Reader::new(BufReader::new(File::open(path)?))?
+        .map(|value| Ok(apache_avro::from_value::<HarvestEvent>(&value?)?));
+    Ok(Box::new(values))
+}
+
+fn read_turtle_dir(path: &Path) -> Result<Events, Error> {
+    let event_type = POSTMAN_TYPE.reasoned_event_type().ok_or_else(|| {
+        Error::Configuration("unable to replay turtle files for unknown postman type".to_string())
+    })?;
+
+    let mut files = std::fs::read_dir(path)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<PathBuf>, Error>>()?;
+    files.retain(|file| file.extension().is_some_and(|extension| extension == "ttl"));
+    files.sort();
+
+    let events = files.into_iter().map(move |file| {
+        let fdk_id = file
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or(format!("invalid file name: {}", file.display()))?
+            .to_string();
+        let timestamp = std::fs::metadata(&file)?
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as i64)
+            .unwrap_or_default();
+
+        Ok(HarvestEvent {
+            event_type,
+            fdk_id,
+            graph: std::fs::read_to_string(&file)?,
+            timestamp,
+        })
+    });
+    Ok(Box::new(events))
+}
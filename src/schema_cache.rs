@@ -0,0 +1,224 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    io::Cursor,
+    path::PathBuf,
+    sync::Arc,
+};
+use apache_avro::{from_avro_datum, schema::Name, Schema};
+use lazy_static::lazy_static;
+use schema_registry_converter::{
+    async_impl::{avro::AvroDecoder, schema_registry::SrSettings},
+    avro_common::DecodeResult,
+    schema_registry_common::{get_bytes_result, BytesResult},
+};
+use crate::{
+    diff_store::{PostmanType, POSTMAN_TYPE},
+    error::Error,
+};
+
+lazy_static! {
+    pub static ref SCHEMA_CACHE_DIR: String =
+        env::var("SCHEMA_CACHE_DIR").unwrap_or("schema-cache".to_string());
+    /// Ids registered for the seed schema of the postman type, which may be
+    /// decoded with the seed schema when they are not in the cache directory.
+    pub static ref SEED_SCHEMA_IDS: HashSet<u32> = env::var("SEED_SCHEMA_IDS")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|id| id.trim().parse().ok())
+        .collect();
+}
+
+// Schemas from `kafka/schemas`, compiled into the binary so that decoding can
+// fall back to them when the registry is unreachable and the cache directory
+// does not have the schema id of a message, if the id is known to be that of
+// the seed schema.
+const SEED_SCHEMAS: [(&str, &str); 3] = [
+    (
+        "no.fdk.concept.ConceptEvent",
+        include_str!("../kafka/schemas/no.fdk.concept.ConceptEvent.json"),
+    ),
+    (
+        "no.fdk.dataservice.DataServiceEvent",
+        include_str!("../kafka/schemas/no.fdk.dataservice.DataServiceEvent.json"),
+    ),
+    (
+        "no.fdk.dataset.DatasetEvent",
+        include_str!("../kafka/schemas/no.fdk.dataset.DatasetEvent.json"),
+    ),
+];
+
+/// Avro decoder that persists every schema fetched from the schema registry to
+/// a local cache directory, and decodes from that cache when the registry
+/// cannot be reached. Schemas the registry does not have are never decoded
+/// from the cache.
+pub struct CachingAvroDecoder<'a> {
+    decoder: AvroDecoder<'a>,
+    cache_dir: PathBuf,
+    seed_schema_ids: HashSet<u32>,
+    persisted: HashSet<u32>,
+    schemas: HashMap<u32, Arc<Schema>>,
+}
+
+impl<'a> CachingAvroDecoder<'a> {
+    pub fn new(sr_settings: SrSettings) -> Self {
+        Self::with_cache_dir(sr_settings, SCHEMA_CACHE_DIR.clone())
+    }
+
+    pub fn with_cache_dir(sr_settings: SrSettings, cache_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            decoder: AvroDecoder::new(sr_settings),
+            cache_dir: cache_dir.into(),
+            seed_schema_ids: SEED_SCHEMA_IDS.clone(),
+            persisted: HashSet::new(),
+            schemas: HashMap::new(),
+        }
+    }
+
+    /// Sets the ids that may be decoded with the seed schema, instead of
+    /// those in `SEED_SCHEMA_IDS`.
+    pub fn with_seed_schema_ids(mut self, ids: impl IntoIterator<Item = u32>) -> Self {
+        self.seed_schema_ids = ids.into_iter().collect();
+        self
+    }
+
+    pub async fn decode(&mut self, bytes: Option<&[u8]>) -> Result<DecodeResult, Error> {
+        match self.decoder.decode_with_schema(bytes).await {
+            Ok(Some(result)) => {
+                self.persist(result.schema.id, &result.schema.raw).await;
+                Ok(DecodeResult {
+                    name: result.name,
+                    value: result.value,
+                })
+            }
+            Ok(None) => Ok(DecodeResult {
+                name: None,
+                value: apache_avro::types::Value::Null,
+            }),
+            Err(e) => {
+                // Errors are cached by the decoder, drop them so that the
+                // registry is asked again once it is reachable.
+                self.decoder.remove_errors_from_cache();
+                // Only connection failures are retriable, any response from
+                // the registry, e.g. a 404 for an unknown id, is final.
+                if !e.retriable {
+                    return Err(e.into());
+                }
+                tracing::warn!(
+                    error = e.to_string(),
+                    "unable to decode using schema registry, falling back to local schema cache"
+                );
+                self.decode_from_cache(bytes).await.map_err(|cache_error| {
+                    tracing::error!(
+                        error = cache_error.to_string(),
+                        "unable to decode using local schema cache"
+                    );
+                    e.into()
+                })
+            }
+        }
+    }
+
+    async fn decode_from_cache(&mut self, bytes: Option<&[u8]>) -> Result<DecodeResult, Error> {
+        let (id, bytes) = match get_bytes_result(bytes) {
+            BytesResult::Valid(id, bytes) => (id, bytes),
+            BytesResult::Null => {
+                return Ok(DecodeResult {
+                    name: None,
+                    value: apache_avro::types::Value::Null,
+                })
+            }
+            BytesResult::Invalid(_) => {
+                return Err(Error::Decode("invalid schema registry wire format".to_string()))
+            }
+        };
+
+        let schema = self.cached_schema(id).await?;
+        let value = from_avro_datum(&schema, &mut Cursor::new(bytes), None)?;
+        Ok(DecodeResult {
+            name: schema_name(&schema),
+            value,
+        })
+    }
+
+    async fn cached_schema(&mut self, id: u32) -> Result<Arc<Schema>, Error> {
+        if let Some(schema) = self.schemas.get(&id) {
+            return Ok(schema.clone());
+        }
+
+        let schema = match tokio::fs::read_to_string(self.schema_path(id)).await {
+            Ok(raw) => Schema::parse_str(&raw)?,
+            Err(e) => {
+                let (seed_name, raw) = seed_schema(&POSTMAN_TYPE)
+                    .filter(|_| self.seed_schema_ids.contains(&id))
+                    .ok_or(Error::UnknownSchema(format!("{} not in local cache: {}", id, e)))?;
+                tracing::warn!(
+                    schema_id = id,
+                    seed_name,
+                    "schema not in local cache, using seed schema"
+                );
+                Schema::parse_str(raw)?
+            }
+        };
+
+        let schema = Arc::new(schema);
+        self.schemas.insert(id, schema.clone());
+        Ok(schema)
+    }
+
+    async fn persist(&mut self, id: u32, raw: &str) {
+        if self.persisted.contains(&id) {
+            return;
+        }
+
+        let path = self.schema_path(id);
+        let tmp_path = path.with_extension("avsc.tmp");
+        let result = async {
+            tokio::fs::create_dir_all(&self.cache_dir).await?;
+            tokio::fs::write(&tmp_path, raw).await?;
+            tokio::fs::rename(&tmp_path, &path).await
+        }
+        .await;
+
+        match result {
+            Ok(_) => {
+                self.persisted.insert(id);
+            }
+            Err(e) => {
+                tracing::warn!(
+                    schema_id = id,
+                    error = e.to_string(),
+                    "unable to persist schema to local cache"
+                );
+            }
+        }
+    }
+
+    fn schema_path(&self, id: u32) -> PathBuf {
+        self.cache_dir.join(format!("{}.avsc", id))
+    }
+}
+
+/// The schema compiled into the binary for events of `postman_type`, with its
+/// full name.
+pub fn seed_schema(postman_type: &PostmanType) -> Option<(&'static str, &'static str)> {
+    let seed_name = match postman_type {
+        PostmanType::Concept => "no.fdk.concept.ConceptEvent",
+        PostmanType::DataService => "no.fdk.dataservice.DataServiceEvent",
+        PostmanType::Dataset => "no.fdk.dataset.DatasetEvent",
+        PostmanType::Unknown => return None,
+    };
+    SEED_SCHEMAS
+        .iter()
+        .find(|(name, _)| *name == seed_name)
+        .copied()
+}
+
+fn schema_name(schema: &Schema) -> Option<Name> {
+    match schema {
+        Schema::Record(record) => Some(record.name.clone()),
+        Schema::Enum(enum_schema) => Some(enum_schema.name.clone()),
+        Schema::Fixed(fixed) => Some(fixed.name.clone()),
+        _ => None,
+    }
+}
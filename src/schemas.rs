@@ -29,6 +29,23 @@ pub enum HarvestEventType {
     Unknown,
 }
 
+impl HarvestEventType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            HarvestEventType::DatasetHarvested => "dataset_harvested",
+            HarvestEventType::DatasetReasoned => "dataset_reasoned",
+            HarvestEventType::DatasetRemoved => "dataset_removed",
+            HarvestEventType::ConceptHarvested => "concept_harvested",
+            HarvestEventType::ConceptReasoned => "concept_reasoned",
+            HarvestEventType::ConceptRemoved => "concept_removed",
+            HarvestEventType::DataServiceHarvested => "data_service_harvested",
+            HarvestEventType::DataServiceReasoned => "data_service_reasoned",
+            HarvestEventType::DataServiceRemoved => "data_service_removed",
+            HarvestEventType::Unknown => "unknown",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HarvestEvent {
     #[serde(rename = "type")]
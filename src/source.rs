@@ -0,0 +1,261 @@
+use std::{collections::VecDeque, future::Future, sync::Mutex, time::Instant};
+use apache_avro::{to_avro_datum, Schema};
+use rdkafka::{
+    consumer::{Consumer, ConsumerGroupMetadata},
+    error::{KafkaError, RDKafkaErrorCode},
+    message::{BorrowedMessage, OwnedHeaders, OwnedMessage},
+    Message, Offset, Timestamp, TopicPartitionList,
+};
+use schema_registry_converter::schema_registry_common::get_payload;
+use crate::{
+    diff_store::POSTMAN_TYPE,
+    error::Error,
+    kafka::{
+        create_consumer, PostmanConsumer, COMMIT_INTERVAL, COMMIT_MODE, INPUT_TOPIC, KAFKA_TIMEOUT,
+    },
+    schema_cache::seed_schema,
+    schemas::HarvestEvent,
+};
+
+/// Where the postman receives messages from. The processing pipeline only
+/// depends on this trait, so that it can run against Kafka or, in tests,
+/// against an in-memory queue.
+pub trait MessageSource: Sync {
+    type Message<'a>: Message + Sync
+    where
+        Self: 'a;
+
+    /// Receives the next message, or `None` when the source is exhausted.
+    fn recv(&self) -> impl Future<Output = Result<Option<Self::Message<'_>>, Error>> + Send;
+
+    /// Marks a message as processed, so that its offset may be committed.
+    fn ack(&self, message: &Self::Message<'_>) -> Result<(), Error>;
+
+    /// Offsets to commit in a producer transaction once `message` is
+    /// processed, or `None` if the source does not support transactions.
+    fn transaction_offsets(
+        &self,
+        _message: &Self::Message<'_>,
+    ) -> Result<Option<(TopicPartitionList, ConsumerGroupMetadata)>, Error> {
+        Ok(None)
+    }
+
+    /// Makes `message` and the messages following it be received again.
+    fn rewind(&self, message: &Self::Message<'_>) -> Result<(), Error>;
+}
+
+/// Message source reading the input topic with the postman consumer group.
+/// Offsets of acknowledged messages are committed every `COMMIT_INTERVAL`,
+/// also while no messages are received.
+pub struct KafkaSource {
+    consumer: PostmanConsumer,
+    commits: Mutex<CommitState>,
+}
+
+struct CommitState {
+    last_commit: Instant,
+    pending: bool,
+}
+
+impl KafkaSource {
+    pub fn new(consumer: PostmanConsumer) -> Self {
+        Self {
+            consumer,
+            commits: Mutex::new(CommitState {
+                last_commit: Instant::now(),
+                pending: false,
+            }),
+        }
+    }
+
+    pub fn create() -> Result<Self, KafkaError> {
+        Ok(Self::new(create_consumer()?))
+    }
+
+    /// Commits the stored offsets if any were stored since the last commit,
+    /// and the commit interval has passed.
+    fn commit_if_due(&self) {
+        let mut commits = self.commits.lock().unwrap_or_else(|e| e.into_inner());
+        if !commits.pending || commits.last_commit.elapsed() < *COMMIT_INTERVAL {
+            return;
+        }
+        commits.last_commit = Instant::now();
+        // Offsets stay pending until committed, so that a failed commit is
+        // retried once the commit interval has passed again, also while no
+        // messages are received.
+        match self.consumer.commit_consumer_state(*COMMIT_MODE) {
+            Ok(_) | Err(KafkaError::ConsumerCommit(RDKafkaErrorCode::NoOffset)) => {
+                commits.pending = false;
+            }
+            Err(e) => tracing::warn!(error = e.to_string(), "failed to commit offsets"),
+        }
+    }
+}
+
+impl MessageSource for KafkaSource {
+    type Message<'a> = BorrowedMessage<'a>;
+
+    async fn recv(&self) -> Result<Option<BorrowedMessage<'_>>, Error> {
+        loop {
+            match tokio::time::timeout(*COMMIT_INTERVAL, self.consumer.recv()).await {
+                Ok(message) => return Ok(Some(message?)),
+                Err(_) => self.commit_if_due(),
+            }
+        }
+    }
+
+    fn ack(&self, message: &BorrowedMessage<'_>) -> Result<(), Error> {
+        self.consumer.store_offset_from_message(message)?;
+        self.commits.lock().unwrap_or_else(|e| e.into_inner()).pending = true;
+        self.commit_if_due();
+        Ok(())
+    }
+
+    fn transaction_offsets(
+        &self,
+        message: &BorrowedMessage<'_>,
+    ) -> Result<Option<(TopicPartitionList, ConsumerGroupMetadata)>, Error> {
+        let mut offsets = TopicPartitionList::new();
+        offsets.add_partition_offset(
+            message.topic(),
+            message.partition(),
+            Offset::Offset(message.offset() + 1),
+        )?;
+        let group_metadata = self
+            .consumer
+            .group_metadata()
+            .ok_or(Error::Unavailable("consumer group metadata".to_string()))?;
+        Ok(Some((offsets, group_metadata)))
+    }
+
+    fn rewind(&self, message: &BorrowedMessage<'_>) -> Result<(), Error> {
+        Ok(self.consumer.seek(
+            message.topic(),
+            message.partition(),
+            Offset::Offset(message.offset()),
+            KAFKA_TIMEOUT,
+        )?)
+    }
+}
+
+/// Message source backed by an in-memory queue of a single partition, with
+/// failures that can be injected between messages.
+pub struct InMemorySource {
+    topic: String,
+    state: Mutex<InMemoryState>,
+}
+
+#[derive(Default)]
+struct InMemoryState {
+    queue: VecDeque<Result<OwnedMessage, Error>>,
+    /// Messages received but not acknowledged, to be enqueued again on rewind.
+    unacked: VecDeque<OwnedMessage>,
+    next_offset: i64,
+    acked: Vec<i64>,
+}
+
+impl Default for InMemorySource {
+    fn default() -> Self {
+        Self::new(INPUT_TOPIC.as_str())
+    }
+}
+
+impl InMemorySource {
+    pub fn new(topic: &str) -> Self {
+        Self {
+            topic: topic.to_string(),
+            state: Mutex::new(InMemoryState::default()),
+        }
+    }
+
+    /// Enqueues a message and returns its offset.
+    pub fn push(
+        &self,
+        key: Option<Vec<u8>>,
+        payload: Option<Vec<u8>>,
+        headers: Option<OwnedHeaders>,
+    ) -> i64 {
+        let mut state = self.lock();
+        let offset = state.next_offset;
+        state.next_offset += 1;
+        state.queue.push_back(Ok(OwnedMessage::new(
+            payload,
+            key,
+            self.topic.clone(),
+            Timestamp::NotAvailable,
+            0,
+            offset,
+            headers,
+        )));
+        offset
+    }
+
+    /// Enqueues an event encoded in the schema registry wire format, with the
+    /// seed schema of the configured postman type, and returns its offset.
+    pub fn push_event(&self, event: &HarvestEvent) -> Result<i64, Error> {
+        let payload = encode_event(event, 1)?;
+        Ok(self.push(Some(event.fdk_id.clone().into_bytes()), Some(payload), None))
+    }
+
+    /// Makes the next `recv` fail with `error`, after the messages already
+    /// enqueued.
+    pub fn push_failure(&self, error: Error) {
+        self.lock().queue.push_back(Err(error));
+    }
+
+    /// Offsets of the messages that were acknowledged, in order.
+    pub fn acked(&self) -> Vec<i64> {
+        self.lock().acked.clone()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().queue.is_empty()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, InMemoryState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl MessageSource for InMemorySource {
+    type Message<'a> = OwnedMessage;
+
+    async fn recv(&self) -> Result<Option<OwnedMessage>, Error> {
+        let mut state = self.lock();
+        let message = state.queue.pop_front().transpose()?;
+        if let Some(message) = &message {
+            state.unacked.push_back(message.clone());
+        }
+        Ok(message)
+    }
+
+    fn ack(&self, message: &OwnedMessage) -> Result<(), Error> {
+        let mut state = self.lock();
+        state.unacked.retain(|unacked| unacked.offset() > message.offset());
+        state.acked.push(message.offset());
+        Ok(())
+    }
+
+    fn rewind(&self, message: &OwnedMessage) -> Result<(), Error> {
+        let mut state = self.lock();
+        let position = state
+            .unacked
+            .iter()
+            .position(|unacked| unacked.offset() >= message.offset())
+            .unwrap_or(state.unacked.len());
+        let rewound = state.unacked.split_off(position);
+        for message in rewound.into_iter().rev() {
+            state.queue.push_front(Ok(message));
+        }
+        Ok(())
+    }
+}
+
+/// Encodes an event in the schema registry wire format.
+pub fn encode_event(event: &HarvestEvent, schema_id: u32) -> Result<Vec<u8>, Error> {
+    let (_, raw) = seed_schema(&POSTMAN_TYPE)
+        .ok_or(Error::UnknownSchema(format!("no seed schema for {}", POSTMAN_TYPE.label())))?;
+    let schema = Schema::parse_str(raw)?;
+    let value = apache_avro::to_value(event)?;
+    Ok(get_payload(schema_id, to_avro_datum(&schema, value)?))
+}
@@ -0,0 +1,125 @@
+use std::env;
+use lazy_static::lazy_static;
+use opentelemetry::{
+    global,
+    propagation::{Extractor, Injector},
+    trace::TracerProvider,
+    Context,
+};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
+use rdkafka::message::Headers;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{filter::LevelFilter, layer::SubscriberExt, util::SubscriberInitExt};
+
+lazy_static! {
+    pub static ref LOG_LEVEL: String = env::var("LOG_LEVEL").unwrap_or("info".to_string());
+    pub static ref LOG_FORMAT: String = env::var("LOG_FORMAT").unwrap_or("json".to_string());
+}
+
+const SERVICE_NAME: &str = "fdk-rdf-postman";
+
+/// Initializes logging and tracing. Spans are always created so that W3C trace
+/// context is propagated from Kafka headers to diff store requests, but they
+/// are only exported when an OTLP endpoint is configured.
+pub fn init_tracing() -> SdkTracerProvider {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let mut provider_builder = SdkTracerProvider::builder()
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build());
+    if otlp_endpoint_configured() {
+        match opentelemetry_otlp::SpanExporter::builder().with_http().build() {
+            Ok(exporter) => provider_builder = provider_builder.with_batch_exporter(exporter),
+            Err(e) => eprintln!("unable to create otlp span exporter: {}", e),
+        }
+    }
+    let provider = provider_builder.build();
+    global::set_tracer_provider(provider.clone());
+
+    let level = LOG_LEVEL.parse::<LevelFilter>().unwrap_or_else(|_| {
+        eprintln!("unknown log level {}, using info", LOG_LEVEL.as_str());
+        LevelFilter::INFO
+    });
+    // Span fields, such as topic, partition, offset and fdkId of the message
+    // being handled, are included in every log event.
+    let (json_layer, pretty_layer) = match LOG_FORMAT.as_str() {
+        "pretty" => (None, Some(tracing_subscriber::fmt::layer().pretty().with_target(false))),
+        format => {
+            if format != "json" {
+                eprintln!("unknown log format {}, using json", format);
+            }
+            let layer = tracing_subscriber::fmt::layer()
+                .json()
+                .with_target(false)
+                .with_current_span(false)
+                .with_span_list(true);
+            (Some(layer), None)
+        }
+    };
+
+    tracing_subscriber::registry()
+        .with(level)
+        .with(json_layer)
+        .with(pretty_layer)
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME)))
+        .init();
+
+    provider
+}
+
+fn otlp_endpoint_configured() -> bool {
+    ["OTEL_EXPORTER_OTLP_ENDPOINT", "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"]
+        .iter()
+        .any(|key| env::var(key).is_ok_and(|value| !value.is_empty()))
+}
+
+/// Sets the parent of `span` to the trace context found in Kafka message
+/// headers, if any.
+pub fn set_parent_from_headers(span: &tracing::Span, headers: Option<&impl Headers>) {
+    let Some(headers) = headers else {
+        return;
+    };
+    let context = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&KafkaHeaderExtractor(headers))
+    });
+    if let Err(e) = span.set_parent(context) {
+        tracing::debug!(error = e.to_string(), "unable to set parent trace context");
+    }
+}
+
+/// Injects the trace context of the current span into outgoing HTTP headers.
+pub fn inject_context(headers: &mut HeaderMap) {
+    let context: Context = tracing::Span::current().context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(headers))
+    });
+}
+
+struct KafkaHeaderExtractor<'a, H>(&'a H);
+
+impl<H: Headers> Extractor for KafkaHeaderExtractor<'_, H> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|header| header.key.eq_ignore_ascii_case(key))
+            .and_then(|header| header.value)
+            .and_then(|value| std::str::from_utf8(value).ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.iter().map(|header| header.key).collect()
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use std::{collections::HashSet, env, sync::OnceLock};
+use oxigraph::{
+    sparql::{PreparedSparqlQuery, PreparedSparqlUpdate, QueryResults, SparqlEvaluator},
+    store::Store,
+};
+use oxrdf::{
+    vocab::xsd, Graph, GraphNameRef, Literal, NamedNode, NamedOrBlankNode, Term, Triple,
+};
+use serde::Deserialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use crate::{error::Error, schemas::HarvestEvent};
+
+static GRAPH_TRANSFORMATIONS: OnceLock<TransformationPipeline> = OnceLock::new();
+
+/// The transformations applied to the graph of every event. Unless a pipeline
+/// was installed with [`install_transformations`], these are the built-in
+/// transformations configured in the environment.
+pub fn graph_transformations() -> &'static TransformationPipeline {
+    GRAPH_TRANSFORMATIONS.get_or_init(|| {
+        TransformationPipeline::from_env().unwrap_or_else(|e| {
+            tracing::error!(error = e.to_string(), "invalid graph transformations");
+            std::process::exit(1);
+        })
+    })
+}
+
+/// Installs the transformations applied to the graph of every event. Call at
+/// startup, before any event is handled, e.g. with the configured
+/// transformations extended with custom ones:
+///
+/// ```no_run
+/// use fdk_rdf_postman::{
+///     error::Error,
+///     schemas::HarvestEvent,
+///     transform::{install_transformations, GraphTransformation, TransformationPipeline},
+/// };
+/// use oxrdf::Graph;
+///
+/// struct Unchanged;
+///
+/// impl GraphTransformation for Unchanged {
+///     fn name(&self) -> &str {
+///         "unchanged"
+///     }
+///
+///     fn apply(&self, graph: Graph, _event: &HarvestEvent) -> Result<Graph, Error> {
+///         Ok(graph)
+///     }
+/// }
+///
+/// install_transformations(TransformationPipeline::from_env()?.with(Box::new(Unchanged)))?;
+/// # Ok::<(), Error>(())
+/// ```
+pub fn install_transformations(pipeline: TransformationPipeline) -> Result<(), Error> {
+    GRAPH_TRANSFORMATIONS
+        .set(pipeline)
+        .map_err(|_| Error::Transformation("graph transformations are already in use".to_string()))
+}
+
+/// A transformation applied to the graph of every event before it is sent to
+/// the diff store. Implement this trait to add transformations that cannot be
+/// expressed with the built-in ones, and add them to the
+/// [`TransformationPipeline`] passed to [`install_transformations`].
+pub trait GraphTransformation: Send + Sync {
+    fn name(&self) -> &str;
+    fn apply(&self, graph: Graph, event: &HarvestEvent) -> Result<Graph, Error>;
+}
+
+/// Configuration of the built-in transformations, read from the JSON array in
+/// `GRAPH_TRANSFORMATIONS`, or from the file at `GRAPH_TRANSFORMATIONS_FILE`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransformationConfig {
+    DropPredicates { predicates: Vec<String> },
+    RewriteIriPrefix { from: String, to: String },
+    Provenance { base_iri: String },
+    SparqlConstruct { query: String },
+    SparqlUpdate { update: String },
+}
+
+impl TransformationConfig {
+    pub fn build(self) -> Result<Box<dyn GraphTransformation>, Error> {
+        Ok(match self {
+            TransformationConfig::DropPredicates { predicates } => {
+                Box::new(DropPredicates::new(&predicates)?)
+            }
+            TransformationConfig::RewriteIriPrefix { from, to } => {
+                Box::new(RewriteIriPrefix { from, to })
+            }
+            TransformationConfig::Provenance { base_iri } => Box::new(Provenance { base_iri }),
+            TransformationConfig::SparqlConstruct { query } => {
+                Box::new(SparqlConstruct::new(query)?)
+            }
+            TransformationConfig::SparqlUpdate { update } => Box::new(SparqlUpdate::new(update)?),
+        })
+    }
+}
+
+/// Transformations applied in order.
+#[derive(Default)]
+pub struct TransformationPipeline {
+    transformations: Vec<Box<dyn GraphTransformation>>,
+}
+
+impl TransformationPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The built-in transformations configured with the JSON array in
+    /// `GRAPH_TRANSFORMATIONS`, or in the file at `GRAPH_TRANSFORMATIONS_FILE`.
+    pub fn from_env() -> Result<Self, Error> {
+        let config = match (
+            env::var("GRAPH_TRANSFORMATIONS"),
+            env::var("GRAPH_TRANSFORMATIONS_FILE"),
+        ) {
+            (Ok(config), _) => config,
+            (_, Ok(path)) => std::fs::read_to_string(&path).map_err(|e| {
+                Error::Transformation(format!("unable to read {}: {}", path, e))
+            })?,
+            _ => return Ok(Self::new()),
+        };
+        let configs = serde_json::from_str(&config)
+            .map_err(|e| Error::Transformation(e.to_string()))?;
+        Self::from_config(configs)
+    }
+
+    pub fn from_config(configs: Vec<TransformationConfig>) -> Result<Self, Error> {
+        let transformations = configs
+            .into_iter()
+            .map(|config| config.build())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { transformations })
+    }
+
+    pub fn with(mut self, transformation: Box<dyn GraphTransformation>) -> Self {
+        self.transformations.push(transformation);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transformations.is_empty()
+    }
+
+    pub fn apply(&self, mut graph: Graph, event: &HarvestEvent) -> Result<Graph, Error> {
+        for transformation in self.transformations.iter() {
+            let triples_before = graph.len();
+            graph = transformation.apply(graph, event)?;
+            tracing::debug!(
+                transformation = transformation.name(),
+                triples_before,
+                triples_after = graph.len(),
+                "applied graph transformation"
+            );
+        }
+        Ok(graph)
+    }
+}
+
+/// Removes all triples with any of the given predicates.
+pub struct DropPredicates {
+    predicates: HashSet<NamedNode>,
+}
+
+impl DropPredicates {
+    pub fn new(predicates: &[String]) -> Result<Self, Error> {
+        let predicates = predicates
+            .iter()
+            .map(|predicate| {
+                NamedNode::new(predicate).map_err(|e| Error::Transformation(e.to_string()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { predicates })
+    }
+}
+
+impl GraphTransformation for DropPredicates {
+    fn name(&self) -> &str {
+        "drop_predicates"
+    }
+
+    fn apply(&self, graph: Graph, _event: &HarvestEvent) -> Result<Graph, Error> {
+        Ok(graph
+            .iter()
+            .filter(|triple| !self.predicates.contains(&triple.predicate.into_owned()))
+            .collect())
+    }
+}
+
+/// Replaces the prefix `from` with `to` in every IRI that starts with `from`.
+pub struct RewriteIriPrefix {
+    pub from: String,
+    pub to: String,
+}
+
+impl RewriteIriPrefix {
+    fn rewrite(&self, node: NamedNode) -> Result<NamedNode, Error> {
+        match node.as_str().strip_prefix(self.from.as_str()) {
+            Some(rest) => NamedNode::new(format!("{}{}", self.to, rest))
+                .map_err(|e| Error::Transformation(e.to_string())),
+            None => Ok(node),
+        }
+    }
+}
+
+impl GraphTransformation for RewriteIriPrefix {
+    fn name(&self) -> &str {
+        "rewrite_iri_prefix"
+    }
+
+    fn apply(&self, graph: Graph, _event: &HarvestEvent) -> Result<Graph, Error> {
+        let mut rewritten = Graph::new();
+        for triple in graph.iter() {
+            let triple = triple.into_owned();
+            let subject = match triple.subject {
+                NamedOrBlankNode::NamedNode(node) => self.rewrite(node)?.into(),
+                subject => subject,
+            };
+            let object = match triple.object {
+                Term::NamedNode(node) => self.rewrite(node)?.into(),
+                object => object,
+            };
+            rewritten.insert(&Triple::new(subject, self.rewrite(triple.predicate)?, object));
+        }
+        Ok(rewritten)
+    }
+}
+
+/// Adds a `{base_iri}{fdkId}` resource with the fdkId and the event timestamp.
+pub struct Provenance {
+    pub base_iri: String,
+}
+
+impl GraphTransformation for Provenance {
+    fn name(&self) -> &str {
+        "provenance"
+    }
+
+    fn apply(&self, mut graph: Graph, event: &HarvestEvent) -> Result<Graph, Error> {
+        let record = NamedNode::new(format!("{}{}", self.base_iri, event.fdk_id))
+            .map_err(|e| Error::Transformation(e.to_string()))?;
+        let timestamp = OffsetDateTime::from_unix_timestamp_nanos(event.timestamp as i128 * 1_000_000)
+            .ok()
+            .and_then(|timestamp| timestamp.format(&Rfc3339).ok())
+            .ok_or(Error::Transformation(format!("invalid timestamp {}", event.timestamp)))?;
+
+        graph.insert(&Triple::new(
+            record.clone(),
+            NamedNode::new_unchecked("http://purl.org/dc/terms/identifier"),
+            Literal::new_simple_literal(&event.fdk_id),
+        ));
+        graph.insert(&Triple::new(
+            record,
+            NamedNode::new_unchecked("http://www.w3.org/ns/prov#generatedAtTime"),
+            Literal::new_typed_literal(timestamp, xsd::DATE_TIME),
+        ));
+        Ok(graph)
+    }
+}
+
+/// Replaces the graph with the result of a SPARQL CONSTRUCT query.
+pub struct SparqlConstruct {
+    query: PreparedSparqlQuery,
+}
+
+impl SparqlConstruct {
+    pub fn new(query: String) -> Result<Self, Error> {
+        let query = SparqlEvaluator::new()
+            .parse_query(&query)
+            .map_err(|e| Error::Transformation(e.to_string()))?;
+        Ok(Self { query })
+    }
+}
+
+impl GraphTransformation for SparqlConstruct {
+    fn name(&self) -> &str {
+        "sparql_construct"
+    }
+
+    fn apply(&self, graph: Graph, _event: &HarvestEvent) -> Result<Graph, Error> {
+        let store = load_store(&graph)?;
+        let results = self
+            .query
+            .clone()
+            .on_store(&store)
+            .execute()
+            .map_err(|e| Error::Transformation(e.to_string()))?;
+        let QueryResults::Graph(triples) = results else {
+            return Err(Error::Transformation("query is not a CONSTRUCT query".to_string()));
+        };
+        triples
+            .collect::<Result<Graph, _>>()
+            .map_err(|e| Error::Transformation(e.to_string()))
+    }
+}
+
+/// Modifies the graph with a SPARQL UPDATE request.
+pub struct SparqlUpdate {
+    update: PreparedSparqlUpdate,
+}
+
+impl SparqlUpdate {
+    pub fn new(update: String) -> Result<Self, Error> {
+        let update = SparqlEvaluator::new()
+            .parse_update(&update)
+            .map_err(|e| Error::Transformation(e.to_string()))?;
+        Ok(Self { update })
+    }
+}
+
+impl GraphTransformation for SparqlUpdate {
+    fn name(&self) -> &str {
+        "sparql_update"
+    }
+
+    fn apply(&self, graph: Graph, _event: &HarvestEvent) -> Result<Graph, Error> {
+        let store = load_store(&graph)?;
+        self.update
+            .clone()
+            .on_store(&store)
+            .execute()
+            .map_err(|e| Error::Transformation(e.to_string()))?;
+        store
+            .quads_for_pattern(None, None, None, Some(GraphNameRef::DefaultGraph))
+            .map(|quad| quad.map(Triple::from))
+            .collect::<Result<Graph, _>>()
+            .map_err(|e| Error::Transformation(e.to_string()))
+    }
+}
+
+fn load_store(graph: &Graph) -> Result<Store, Error> {
+    let store = Store::new().map_err(|e| Error::Transformation(e.to_string()))?;
+    store
+        .extend(
+            graph
+                .iter()
+                .map(|triple| triple.into_owned().in_graph(GraphNameRef::DefaultGraph)),
+        )
+        .map_err(|e| Error::Transformation(e.to_string()))?;
+    Ok(store)
+}
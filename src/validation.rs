@@ -0,0 +1,1255 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fmt,
+    path::Path as FsPath,
+    sync::OnceLock,
+};
+use lazy_static::lazy_static;
+use oxrdf::{
+    vocab::{rdf, rdfs},
+    BlankNode, Graph, Literal, NamedNode, NamedNodeRef, NamedOrBlankNode, NamedOrBlankNodeRef,
+    Term, TermRef, Triple,
+};
+use oxrdfio::RdfFormat;
+use rdkafka::producer::FutureProducer;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use crate::{
+    diff_store::POSTMAN_TYPE,
+    error::Error,
+    kafka::{create_producer, produce},
+    metrics::{SHACL_VALIDATIONS, SHACL_VALIDATION_RESULTS},
+    rdf::parse_graph,
+};
+
+lazy_static! {
+    pub static ref SHACL_SHAPES_DIR: String =
+        env::var("SHACL_SHAPES_DIR").unwrap_or("shapes".to_string());
+    pub static ref SHACL_POLICY: ValidationPolicy =
+        ValidationPolicy::parse(&env::var("SHACL_POLICY").unwrap_or("warn".to_string()));
+    pub static ref SHACL_REPORT_TOPIC: Option<String> =
+        env::var("SHACL_REPORT_TOPIC").ok().filter(|topic| !topic.is_empty());
+    static ref REPORT_PRODUCER: Option<FutureProducer> = SHACL_REPORT_TOPIC.as_ref().and_then(|_| {
+        create_producer()
+            .map_err(|e| {
+                tracing::error!(error = e.to_string(), "unable to create shacl report producer");
+            })
+            .ok()
+    });
+}
+
+/// Shapes of this postman type, set by `load_shapes`.
+static SHAPES: OnceLock<Option<Shapes>> = OnceLock::new();
+
+const SH: &str = "http://www.w3.org/ns/shacl#";
+
+fn sh(name: &str) -> NamedNode {
+    NamedNode::new_unchecked(format!("{}{}", SH, name))
+}
+
+/// What to do with graphs that do not conform to the shapes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationPolicy {
+    /// Graphs are not validated.
+    Off,
+    /// Results are reported, and the graph is forwarded unchanged.
+    Warn,
+    /// Graphs with violations are not forwarded.
+    Block,
+    /// The validation report is added to the forwarded graph.
+    Annotate,
+}
+
+impl ValidationPolicy {
+    fn parse(policy: &str) -> Self {
+        match policy {
+            "off" => ValidationPolicy::Off,
+            "warn" => ValidationPolicy::Warn,
+            "block" => ValidationPolicy::Block,
+            "annotate" => ValidationPolicy::Annotate,
+            _ => {
+                tracing::error!(policy, "unknown shacl policy, using warn");
+                ValidationPolicy::Warn
+            }
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ValidationPolicy::Off => "off",
+            ValidationPolicy::Warn => "warn",
+            ValidationPolicy::Block => "block",
+            ValidationPolicy::Annotate => "annotate",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Severity {
+    Violation,
+    Warning,
+    Info,
+}
+
+impl Severity {
+    fn from_iri(iri: NamedNodeRef<'_>) -> Self {
+        match iri.as_str().strip_prefix(SH) {
+            Some("Warning") => Severity::Warning,
+            Some("Info") => Severity::Info,
+            _ => Severity::Violation,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Violation => "violation",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        }
+    }
+}
+
+/// A SHACL property path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Path {
+    Predicate(NamedNode),
+    Inverse(Box<Path>),
+    Sequence(Vec<Path>),
+    Alternative(Vec<Path>),
+    ZeroOrMore(Box<Path>),
+    OneOrMore(Box<Path>),
+    ZeroOrOne(Box<Path>),
+}
+
+impl Path {
+    /// The nodes reached from `focus_node` by following the path in `data`.
+    fn values(&self, data: &Graph, focus_node: TermRef<'_>) -> Vec<Term> {
+        let mut values = Vec::new();
+        let mut seen = HashSet::new();
+        self.collect_values(data, focus_node, &mut |value| {
+            if seen.insert(value.clone()) {
+                values.push(value);
+            }
+        });
+        values
+    }
+
+    fn collect_values(&self, data: &Graph, node: TermRef<'_>, add: &mut dyn FnMut(Term)) {
+        match self {
+            Path::Predicate(predicate) => {
+                if let Some(subject) = as_subject(node) {
+                    data.objects_for_subject_predicate(subject, predicate)
+                        .for_each(|object| add(object.into_owned()));
+                }
+            }
+            Path::Inverse(path) => match path.as_ref() {
+                Path::Predicate(predicate) => data
+                    .subjects_for_predicate_object(predicate, node)
+                    .for_each(|subject| add(subject.into_owned().into())),
+                path => {
+                    // Nodes from which the inner path reaches `node`.
+                    let candidates: HashSet<Term> = data
+                        .iter()
+                        .flat_map(|triple| [triple.subject.into_owned().into(), triple.object.into_owned()])
+                        .collect();
+                    for candidate in candidates {
+                        if path.values(data, candidate.as_ref()).iter().any(|value| value.as_ref() == node) {
+                            add(candidate);
+                        }
+                    }
+                }
+            },
+            Path::Sequence(paths) => {
+                let mut current = vec![node.into_owned()];
+                for path in paths {
+                    let mut next = Vec::new();
+                    let mut seen = HashSet::new();
+                    for node in current.iter() {
+                        path.collect_values(data, node.as_ref(), &mut |value| {
+                            if seen.insert(value.clone()) {
+                                next.push(value);
+                            }
+                        });
+                    }
+                    current = next;
+                }
+                current.into_iter().for_each(add);
+            }
+            Path::Alternative(paths) => {
+                for path in paths {
+                    path.collect_values(data, node, add);
+                }
+            }
+            Path::ZeroOrMore(path) | Path::OneOrMore(path) => {
+                let mut reached = HashSet::new();
+                let mut queue = vec![node.into_owned()];
+                if matches!(self, Path::ZeroOrMore(_)) {
+                    reached.insert(node.into_owned());
+                    add(node.into_owned());
+                }
+                while let Some(next) = queue.pop() {
+                    path.collect_values(data, next.as_ref(), &mut |value| {
+                        if reached.insert(value.clone()) {
+                            queue.push(value.clone());
+                            add(value);
+                        }
+                    });
+                }
+            }
+            Path::ZeroOrOne(path) => {
+                add(node.into_owned());
+                path.collect_values(data, node, add);
+            }
+        }
+    }
+
+    /// Adds the path to `graph` in its SHACL syntax, and returns its node.
+    fn to_graph(&self, graph: &mut Graph) -> Term {
+        let path_node = |predicate: &str, path: &Path, graph: &mut Graph| -> Term {
+            let node = BlankNode::default();
+            let inner = path.to_graph(graph);
+            graph.insert(&Triple::new(node.clone(), sh(predicate), inner));
+            node.into()
+        };
+        match self {
+            Path::Predicate(predicate) => predicate.clone().into(),
+            Path::Inverse(path) => path_node("inversePath", path, graph),
+            Path::ZeroOrMore(path) => path_node("zeroOrMorePath", path, graph),
+            Path::OneOrMore(path) => path_node("oneOrMorePath", path, graph),
+            Path::ZeroOrOne(path) => path_node("zeroOrOnePath", path, graph),
+            Path::Sequence(paths) => list_to_graph(paths, graph),
+            Path::Alternative(paths) => {
+                let node = BlankNode::default();
+                let list = list_to_graph(paths, graph);
+                graph.insert(&Triple::new(node.clone(), sh("alternativePath"), list));
+                node.into()
+            }
+        }
+    }
+}
+
+fn list_to_graph(paths: &[Path], graph: &mut Graph) -> Term {
+    let mut list: Term = rdf::NIL.into_owned().into();
+    for path in paths.iter().rev() {
+        let item = path.to_graph(graph);
+        let node = BlankNode::default();
+        graph.insert(&Triple::new(node.clone(), rdf::FIRST, item));
+        graph.insert(&Triple::new(node.clone(), rdf::REST, list));
+        list = node.into();
+    }
+    list
+}
+
+impl fmt::Display for Path {
+    /// Formats the path in SPARQL property path syntax.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |paths: &[Path], separator: &str| {
+            paths.iter().map(|path| path.to_string()).collect::<Vec<_>>().join(separator)
+        };
+        match self {
+            Path::Predicate(predicate) => write!(f, "{}", predicate),
+            Path::Inverse(path) => write!(f, "^{}", path),
+            Path::Sequence(paths) => write!(f, "({})", join(paths, "/")),
+            Path::Alternative(paths) => write!(f, "({})", join(paths, "|")),
+            Path::ZeroOrMore(path) => write!(f, "{}*", path),
+            Path::OneOrMore(path) => write!(f, "{}+", path),
+            Path::ZeroOrOne(path) => write!(f, "{}?", path),
+        }
+    }
+}
+
+/// Shapes graph for the SHACL Core subset supported by the postman:
+///
+/// - class, node, subjects-of and objects-of targets, including implicit class
+///   targets,
+/// - all property paths,
+/// - value type (`class`, `datatype`, `nodeKind`), cardinality, string
+///   (`pattern`, `minLength`, `maxLength`, `languageIn`, `uniqueLang`),
+///   `in`, `hasValue`, logical (`not`, `and`, `or`, `xone`), `node`,
+///   `property` and `closed` constraints,
+/// - `deactivated`, `severity` and `message`.
+///
+/// Class targets and constraints follow `rdfs:subClassOf` in the data graph.
+/// Shapes using any other SHACL construct are rejected when parsed, rather
+/// than silently validating less than they declare.
+#[derive(Debug)]
+pub struct Shapes {
+    shapes: Vec<Shape>,
+}
+
+#[derive(Debug)]
+struct Shape {
+    id: Term,
+    path: Option<Path>,
+    deactivated: bool,
+    severity: Severity,
+    message: Option<String>,
+    target_classes: Vec<NamedNode>,
+    target_nodes: Vec<Term>,
+    target_subjects_of: Vec<NamedNode>,
+    target_objects_of: Vec<NamedNode>,
+    constraints: Vec<Constraint>,
+    /// Indexes of the property shapes of the shape.
+    properties: Vec<usize>,
+}
+
+#[derive(Debug)]
+enum Constraint {
+    MinCount(usize),
+    MaxCount(usize),
+    Datatype(NamedNode),
+    Class(NamedNode),
+    NodeKind(NamedNode),
+    Pattern(Regex),
+    MinLength(usize),
+    MaxLength(usize),
+    LanguageIn(Vec<String>),
+    UniqueLang,
+    In(Vec<Term>),
+    HasValue(Term),
+    Node(usize),
+    Not(usize),
+    And(Vec<usize>),
+    Or(Vec<usize>),
+    Xone(Vec<usize>),
+    /// Predicates allowed on focus nodes of a closed shape.
+    Closed(Vec<NamedNode>),
+}
+
+impl Constraint {
+    fn component(&self) -> &'static str {
+        match self {
+            Constraint::MinCount(_) => "MinCountConstraintComponent",
+            Constraint::MaxCount(_) => "MaxCountConstraintComponent",
+            Constraint::Datatype(_) => "DatatypeConstraintComponent",
+            Constraint::Class(_) => "ClassConstraintComponent",
+            Constraint::NodeKind(_) => "NodeKindConstraintComponent",
+            Constraint::Pattern(_) => "PatternConstraintComponent",
+            Constraint::MinLength(_) => "MinLengthConstraintComponent",
+            Constraint::MaxLength(_) => "MaxLengthConstraintComponent",
+            Constraint::LanguageIn(_) => "LanguageInConstraintComponent",
+            Constraint::UniqueLang => "UniqueLangConstraintComponent",
+            Constraint::In(_) => "InConstraintComponent",
+            Constraint::HasValue(_) => "HasValueConstraintComponent",
+            Constraint::Node(_) => "NodeConstraintComponent",
+            Constraint::Not(_) => "NotConstraintComponent",
+            Constraint::And(_) => "AndConstraintComponent",
+            Constraint::Or(_) => "OrConstraintComponent",
+            Constraint::Xone(_) => "XoneConstraintComponent",
+            Constraint::Closed(_) => "ClosedConstraintComponent",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidationResult {
+    pub focus_node: Term,
+    /// Path of the property shape, or the predicate of a closed shape
+    /// violation. `None` for node shape constraints.
+    pub path: Option<Path>,
+    pub value: Option<Term>,
+    pub source_shape: Term,
+    pub constraint: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub results: Vec<ValidationResult>,
+}
+
+impl ValidationReport {
+    pub fn conforms(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    pub fn violations(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|result| result.severity == Severity::Violation)
+            .count()
+    }
+
+    /// The report as a `sh:ValidationReport` graph.
+    pub fn to_graph(&self) -> Graph {
+        let mut graph = Graph::new();
+        let report = BlankNode::default();
+        graph.insert(&Triple::new(report.clone(), rdf::TYPE, sh("ValidationReport")));
+        graph.insert(&Triple::new(
+            report.clone(),
+            sh("conforms"),
+            Literal::from(self.conforms()),
+        ));
+        for result in self.results.iter() {
+            let node = BlankNode::default();
+            graph.insert(&Triple::new(report.clone(), sh("result"), node.clone()));
+            graph.insert(&Triple::new(node.clone(), rdf::TYPE, sh("ValidationResult")));
+            graph.insert(&Triple::new(node.clone(), sh("focusNode"), result.focus_node.clone()));
+            if let Some(path) = &result.path {
+                let path = path.to_graph(&mut graph);
+                graph.insert(&Triple::new(node.clone(), sh("resultPath"), path));
+            }
+            if let Some(value) = &result.value {
+                graph.insert(&Triple::new(node.clone(), sh("value"), value.clone()));
+            }
+            graph.insert(&Triple::new(node.clone(), sh("sourceShape"), result.source_shape.clone()));
+            graph.insert(&Triple::new(
+                node.clone(),
+                sh("sourceConstraintComponent"),
+                sh(result.constraint),
+            ));
+            let severity = match result.severity {
+                Severity::Violation => "Violation",
+                Severity::Warning => "Warning",
+                Severity::Info => "Info",
+            };
+            graph.insert(&Triple::new(node.clone(), sh("resultSeverity"), sh(severity)));
+            graph.insert(&Triple::new(
+                node,
+                sh("resultMessage"),
+                Literal::new_simple_literal(&result.message),
+            ));
+        }
+        graph
+    }
+}
+
+/// SHACL parameters that do not affect validation, or are read together with
+/// another parameter.
+const IGNORED_PARAMETERS: [&str; 7] = [
+    "name",
+    "description",
+    "order",
+    "group",
+    "defaultValue",
+    "flags",
+    "ignoredProperties",
+];
+
+impl Shapes {
+    /// Parses the shapes of `shapes_graph`. Fails with every unsupported
+    /// construct used by the shapes.
+    pub fn parse(shapes_graph: &Graph) -> Result<Self, Error> {
+        let mut parser = ShapesParser {
+            graph: shapes_graph,
+            shapes: Vec::new(),
+            index: HashMap::new(),
+            problems: Vec::new(),
+        };
+        let mut roots: Vec<NamedOrBlankNode> = Vec::new();
+        for shape_type in ["NodeShape", "PropertyShape"] {
+            roots.extend(
+                shapes_graph
+                    .subjects_for_predicate_object(rdf::TYPE, sh(shape_type).as_ref())
+                    .map(|subject| subject.into_owned()),
+            );
+        }
+        for target in ["targetClass", "targetNode", "targetSubjectsOf", "targetObjectsOf"] {
+            roots.extend(
+                shapes_graph
+                    .triples_for_predicate(sh(target).as_ref())
+                    .map(|triple| triple.subject.into_owned()),
+            );
+        }
+        for root in sorted_unique(roots).iter() {
+            parser.shape(root.as_ref());
+        }
+
+        if !parser.problems.is_empty() {
+            parser.problems.sort();
+            parser.problems.dedup();
+            return Err(Error::Configuration(format!(
+                "unsupported shacl shapes: {}",
+                parser.problems.join("; ")
+            )));
+        }
+        Ok(Shapes {
+            shapes: parser.shapes.into_iter().map(|shape| shape.expect("shape parsed")).collect(),
+        })
+    }
+
+    pub fn validate(&self, data: &Graph) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        for (index, shape) in self.shapes.iter().enumerate() {
+            if shape.deactivated {
+                continue;
+            }
+            for focus_node in shape.focus_nodes(data) {
+                self.validate_shape(data, index, &focus_node, &mut report.results);
+            }
+        }
+        report
+    }
+
+    fn validate_shape(
+        &self,
+        data: &Graph,
+        index: usize,
+        focus_node: &Term,
+        results: &mut Vec<ValidationResult>,
+    ) {
+        let shape = &self.shapes[index];
+        if shape.deactivated {
+            return;
+        }
+        let values = match &shape.path {
+            Some(path) => path.values(data, focus_node.as_ref()),
+            None => vec![focus_node.clone()],
+        };
+        let mut add_result = |constraint: &Constraint, path: Option<Path>, value: Option<&Term>, default: String| {
+            results.push(ValidationResult {
+                focus_node: focus_node.clone(),
+                path,
+                value: value.cloned(),
+                source_shape: shape.id.clone(),
+                constraint: constraint.component(),
+                severity: shape.severity,
+                message: shape.message.clone().unwrap_or(default),
+            });
+        };
+        let path = shape.path.clone();
+        let subject = shape
+            .path
+            .as_ref()
+            .map_or("focus node".to_string(), |path| format!("value of {}", path));
+
+        for constraint in shape.constraints.iter() {
+            match constraint {
+                Constraint::MinCount(min_count) => {
+                    if values.len() < *min_count {
+                        add_result(constraint, path.clone(), None, format!("less than {} values of {}", min_count, display(&path)));
+                    }
+                }
+                Constraint::MaxCount(max_count) => {
+                    if values.len() > *max_count {
+                        add_result(constraint, path.clone(), None, format!("more than {} values of {}", max_count, display(&path)));
+                    }
+                }
+                Constraint::UniqueLang => {
+                    let mut languages = HashMap::new();
+                    for value in values.iter() {
+                        if let Term::Literal(literal) = value {
+                            if let Some(language) = literal.language() {
+                                *languages.entry(language.to_lowercase()).or_insert(0) += 1;
+                            }
+                        }
+                    }
+                    let mut duplicates: Vec<_> = languages
+                        .into_iter()
+                        .filter(|(_, count)| *count > 1)
+                        .map(|(language, _)| language)
+                        .collect();
+                    duplicates.sort();
+                    for language in duplicates {
+                        add_result(constraint, path.clone(), None, format!("more than one value of {} in language {}", display(&path), language));
+                    }
+                }
+                Constraint::HasValue(expected) => {
+                    if !values.contains(expected) {
+                        add_result(constraint, path.clone(), None, format!("{} does not have value {}", subject, expected));
+                    }
+                }
+                Constraint::Closed(allowed) => {
+                    for value in values.iter() {
+                        let Some(value_subject) = as_subject(value.as_ref()) else {
+                            continue;
+                        };
+                        for triple in data.triples_for_subject(value_subject) {
+                            if !allowed.iter().any(|predicate| predicate.as_ref() == triple.predicate) {
+                                let object = triple.object.into_owned();
+                                add_result(
+                                    constraint,
+                                    Some(Path::Predicate(triple.predicate.into_owned())),
+                                    Some(&object),
+                                    format!("predicate {} is not allowed by closed shape", triple.predicate),
+                                );
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    for value in values.iter() {
+                        if let Some(message) = self.value_violation(data, constraint, value, &subject) {
+                            add_result(constraint, path.clone(), Some(value), message);
+                        }
+                    }
+                }
+            }
+        }
+
+        for property in shape.properties.iter() {
+            for value in values.iter() {
+                self.validate_shape(data, *property, value, results);
+            }
+        }
+    }
+
+    /// Checks a constraint applying to each value node on its own, returning
+    /// the default message if `value` violates it.
+    fn value_violation(
+        &self,
+        data: &Graph,
+        constraint: &Constraint,
+        value: &Term,
+        subject: &str,
+    ) -> Option<String> {
+        let conforms = |shape: &usize| self.conforms(data, *shape, value);
+        let (valid, message) = match constraint {
+            Constraint::Datatype(datatype) => (
+                matches!(value, Term::Literal(literal) if literal.datatype() == datatype.as_ref()),
+                format!("{} is not of datatype {}", subject, datatype),
+            ),
+            Constraint::Class(class) => (
+                is_instance(data, value.as_ref(), class),
+                format!("{} is not an instance of {}", subject, class),
+            ),
+            Constraint::NodeKind(node_kind) => (
+                node_kind_matches(node_kind, value.as_ref()),
+                format!("{} is not of node kind {}", subject, node_kind),
+            ),
+            Constraint::Pattern(pattern) => (
+                lexical_form(value).is_some_and(|form| pattern.is_match(form)),
+                format!("{} does not match pattern {}", subject, pattern),
+            ),
+            Constraint::MinLength(min_length) => (
+                lexical_form(value).is_some_and(|form| form.chars().count() >= *min_length),
+                format!("{} is shorter than {} characters", subject, min_length),
+            ),
+            Constraint::MaxLength(max_length) => (
+                lexical_form(value).is_some_and(|form| form.chars().count() <= *max_length),
+                format!("{} is longer than {} characters", subject, max_length),
+            ),
+            Constraint::LanguageIn(languages) => (
+                match value {
+                    Term::Literal(literal) => literal
+                        .language()
+                        .is_some_and(|language| languages.iter().any(|range| language_matches(language, range))),
+                    _ => false,
+                },
+                format!("{} is not in one of the languages {}", subject, languages.join(", ")),
+            ),
+            Constraint::In(allowed) => (
+                allowed.contains(value),
+                format!("{} is not one of the allowed values", subject),
+            ),
+            Constraint::Node(shape) => (
+                conforms(shape),
+                format!("{} does not conform to {}", subject, self.shapes[*shape].id),
+            ),
+            Constraint::Not(shape) => (
+                !conforms(shape),
+                format!("{} conforms to {}", subject, self.shapes[*shape].id),
+            ),
+            Constraint::And(shapes) => (
+                shapes.iter().all(conforms),
+                format!("{} does not conform to all shapes", subject),
+            ),
+            Constraint::Or(shapes) => (
+                shapes.iter().any(conforms),
+                format!("{} does not conform to any shape", subject),
+            ),
+            Constraint::Xone(shapes) => (
+                shapes.iter().filter(|shape| conforms(shape)).count() == 1,
+                format!("{} does not conform to exactly one shape", subject),
+            ),
+            Constraint::MinCount(_)
+            | Constraint::MaxCount(_)
+            | Constraint::UniqueLang
+            | Constraint::HasValue(_)
+            | Constraint::Closed(_) => (true, String::new()),
+        };
+        (!valid).then_some(message)
+    }
+
+    fn conforms(&self, data: &Graph, shape: usize, node: &Term) -> bool {
+        let mut results = Vec::new();
+        self.validate_shape(data, shape, node, &mut results);
+        results.is_empty()
+    }
+}
+
+fn display(path: &Option<Path>) -> String {
+    path.as_ref().map_or("focus node".to_string(), |path| path.to_string())
+}
+
+struct ShapesParser<'a> {
+    graph: &'a Graph,
+    /// Parsed shapes, `None` while a shape is being parsed.
+    shapes: Vec<Option<Shape>>,
+    index: HashMap<NamedOrBlankNode, usize>,
+    problems: Vec<String>,
+}
+
+impl ShapesParser<'_> {
+    /// Parses the shape `id` and the shapes it references, and returns its
+    /// index.
+    fn shape(&mut self, id: NamedOrBlankNodeRef<'_>) -> usize {
+        if let Some(index) = self.index.get(&id.into_owned()) {
+            if self.shapes[*index].is_none() {
+                self.problems.push(format!("recursive reference to shape {}", id));
+            }
+            return *index;
+        }
+        let index = self.shapes.len();
+        self.shapes.push(None);
+        self.index.insert(id.into_owned(), index);
+        let shape = self.parse_shape(id);
+        self.shapes[index] = Some(shape);
+        index
+    }
+
+    fn parse_shape(&mut self, id: NamedOrBlankNodeRef<'_>) -> Shape {
+        let graph = self.graph;
+        let mut shape = Shape {
+            id: id.into_owned().into(),
+            path: None,
+            deactivated: false,
+            severity: Severity::Violation,
+            message: None,
+            target_classes: Vec::new(),
+            target_nodes: Vec::new(),
+            target_subjects_of: Vec::new(),
+            target_objects_of: Vec::new(),
+            constraints: Vec::new(),
+            properties: Vec::new(),
+        };
+        // Shapes that are also classes target the instances of themselves.
+        if let NamedOrBlankNodeRef::NamedNode(node) = id {
+            if graph.contains(&Triple::new(node, rdf::TYPE, rdfs::CLASS)) {
+                shape.target_classes.push(node.into_owned());
+            }
+        }
+        let mut closed = false;
+
+        for triple in graph.triples_for_subject(id) {
+            let Some(parameter) = triple.predicate.as_str().strip_prefix(SH) else {
+                continue;
+            };
+            if IGNORED_PARAMETERS.contains(&parameter) {
+                continue;
+            }
+            let object = triple.object;
+            let invalid = |problems: &mut Vec<String>| {
+                problems.push(format!("invalid value {} of sh:{} in shape {}", object, parameter, id));
+            };
+            match parameter {
+                "path" => match self.path(object) {
+                    Ok(path) => shape.path = Some(path),
+                    Err(problem) => self.problems.push(format!("{} in shape {}", problem, id)),
+                },
+                "targetClass" => match object {
+                    TermRef::NamedNode(class) => shape.target_classes.push(class.into_owned()),
+                    _ => invalid(&mut self.problems),
+                },
+                "targetNode" => shape.target_nodes.push(object.into_owned()),
+                "targetSubjectsOf" | "targetObjectsOf" => match object {
+                    TermRef::NamedNode(predicate) if parameter == "targetSubjectsOf" => {
+                        shape.target_subjects_of.push(predicate.into_owned())
+                    }
+                    TermRef::NamedNode(predicate) => shape.target_objects_of.push(predicate.into_owned()),
+                    _ => invalid(&mut self.problems),
+                },
+                "severity" => match object {
+                    TermRef::NamedNode(severity) => shape.severity = Severity::from_iri(severity),
+                    _ => invalid(&mut self.problems),
+                },
+                "message" => match object {
+                    TermRef::Literal(literal) if shape.message.is_none() => {
+                        shape.message = Some(literal.value().to_string())
+                    }
+                    TermRef::Literal(_) => {}
+                    _ => invalid(&mut self.problems),
+                },
+                "deactivated" => match boolean(object) {
+                    Some(deactivated) => shape.deactivated = deactivated,
+                    None => invalid(&mut self.problems),
+                },
+                "closed" => match boolean(object) {
+                    Some(value) => closed = value,
+                    None => invalid(&mut self.problems),
+                },
+                "uniqueLang" => match boolean(object) {
+                    Some(true) => shape.constraints.push(Constraint::UniqueLang),
+                    Some(false) => {}
+                    None => invalid(&mut self.problems),
+                },
+                "minCount" | "maxCount" | "minLength" | "maxLength" => {
+                    let Some(count) = integer(object) else {
+                        invalid(&mut self.problems);
+                        continue;
+                    };
+                    shape.constraints.push(match parameter {
+                        "minCount" => Constraint::MinCount(count),
+                        "maxCount" => Constraint::MaxCount(count),
+                        "minLength" => Constraint::MinLength(count),
+                        _ => Constraint::MaxLength(count),
+                    });
+                }
+                "datatype" | "class" | "nodeKind" => {
+                    let TermRef::NamedNode(node) = object else {
+                        invalid(&mut self.problems);
+                        continue;
+                    };
+                    let node = node.into_owned();
+                    shape.constraints.push(match parameter {
+                        "datatype" => Constraint::Datatype(node),
+                        "class" => Constraint::Class(node),
+                        _ => Constraint::NodeKind(node),
+                    });
+                }
+                "pattern" => match self.pattern(id, object) {
+                    Ok(pattern) => shape.constraints.push(Constraint::Pattern(pattern)),
+                    Err(problem) => self.problems.push(problem),
+                },
+                "languageIn" => match self.list(object) {
+                    Ok(languages) => {
+                        let languages = languages
+                            .iter()
+                            .filter_map(|language| match language {
+                                Term::Literal(literal) => Some(literal.value().to_string()),
+                                _ => None,
+                            })
+                            .collect();
+                        shape.constraints.push(Constraint::LanguageIn(languages));
+                    }
+                    Err(problem) => self.problems.push(format!("{} in shape {}", problem, id)),
+                },
+                "in" => match self.list(object) {
+                    Ok(values) => shape.constraints.push(Constraint::In(values)),
+                    Err(problem) => self.problems.push(format!("{} in shape {}", problem, id)),
+                },
+                "hasValue" => shape.constraints.push(Constraint::HasValue(object.into_owned())),
+                "property" | "node" | "not" => {
+                    let Some(reference) = as_subject(object) else {
+                        invalid(&mut self.problems);
+                        continue;
+                    };
+                    let reference = self.shape(reference);
+                    match parameter {
+                        "property" => shape.properties.push(reference),
+                        "node" => shape.constraints.push(Constraint::Node(reference)),
+                        _ => shape.constraints.push(Constraint::Not(reference)),
+                    }
+                }
+                "and" | "or" | "xone" => {
+                    let members = match self.list(object) {
+                        Ok(members) => members,
+                        Err(problem) => {
+                            self.problems.push(format!("{} in shape {}", problem, id));
+                            continue;
+                        }
+                    };
+                    let mut shapes = Vec::new();
+                    for member in members.iter() {
+                        match as_subject(member.as_ref()) {
+                            Some(member) => shapes.push(self.shape(member)),
+                            None => invalid(&mut self.problems),
+                        }
+                    }
+                    shape.constraints.push(match parameter {
+                        "and" => Constraint::And(shapes),
+                        "or" => Constraint::Or(shapes),
+                        _ => Constraint::Xone(shapes),
+                    });
+                }
+                parameter => self.problems.push(format!("sh:{} in shape {}", parameter, id)),
+            }
+        }
+
+        if closed {
+            match self.closed_predicates(id, &shape) {
+                Ok(allowed) => shape.constraints.push(Constraint::Closed(allowed)),
+                Err(problem) => self.problems.push(problem),
+            }
+        }
+        shape
+    }
+
+    fn path(&self, node: TermRef<'_>) -> Result<Path, String> {
+        let subject = match node {
+            TermRef::NamedNode(predicate) => return Ok(Path::Predicate(predicate.into_owned())),
+            TermRef::BlankNode(node) => NamedOrBlankNodeRef::from(node),
+            _ => return Err(format!("invalid sh:path {}", node)),
+        };
+        if self.graph.object_for_subject_predicate(subject, rdf::FIRST).is_some() {
+            let paths = self
+                .list(node)?
+                .iter()
+                .map(|path| self.path(path.as_ref()))
+                .collect::<Result<Vec<_>, _>>()?;
+            if paths.len() < 2 {
+                return Err(format!("sequence path {} with less than two members", node));
+            }
+            return Ok(Path::Sequence(paths));
+        }
+        let mut triples = self.graph.triples_for_subject(subject);
+        let (Some(triple), None) = (triples.next(), triples.next()) else {
+            return Err(format!("invalid sh:path {}", node));
+        };
+        let inner = || self.path(triple.object).map(Box::new);
+        match triple.predicate.as_str().strip_prefix(SH) {
+            Some("inversePath") => Ok(Path::Inverse(inner()?)),
+            Some("zeroOrMorePath") => Ok(Path::ZeroOrMore(inner()?)),
+            Some("oneOrMorePath") => Ok(Path::OneOrMore(inner()?)),
+            Some("zeroOrOnePath") => Ok(Path::ZeroOrOne(inner()?)),
+            Some("alternativePath") => Ok(Path::Alternative(
+                self.list(triple.object)?
+                    .iter()
+                    .map(|path| self.path(path.as_ref()))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )),
+            _ => Err(format!("invalid sh:path {}", node)),
+        }
+    }
+
+    /// The members of the RDF list starting at `head`.
+    fn list(&self, head: TermRef<'_>) -> Result<Vec<Term>, String> {
+        let mut members = Vec::new();
+        let mut node = head;
+        while node != rdf::NIL.into() {
+            let first = as_subject(node)
+                .and_then(|subject| self.graph.object_for_subject_predicate(subject, rdf::FIRST));
+            let rest = as_subject(node)
+                .and_then(|subject| self.graph.object_for_subject_predicate(subject, rdf::REST));
+            let (Some(first), Some(rest)) = (first, rest) else {
+                return Err(format!("invalid rdf list {}", head));
+            };
+            members.push(first.into_owned());
+            node = rest;
+        }
+        Ok(members)
+    }
+
+    fn pattern(&self, id: NamedOrBlankNodeRef<'_>, pattern: TermRef<'_>) -> Result<Regex, String> {
+        let TermRef::Literal(pattern) = pattern else {
+            return Err(format!("invalid sh:pattern {} in shape {}", pattern, id));
+        };
+        let flags = match self.graph.object_for_subject_predicate(id, sh("flags").as_ref()) {
+            Some(TermRef::Literal(flags)) => flags.value().to_string(),
+            _ => String::new(),
+        };
+        let source = match flags.contains('q') {
+            true => regex::escape(pattern.value()),
+            false => pattern.value().to_string(),
+        };
+        let mut builder = RegexBuilder::new(&source);
+        for flag in flags.chars() {
+            match flag {
+                'i' => builder.case_insensitive(true),
+                's' => builder.dot_matches_new_line(true),
+                'm' => builder.multi_line(true),
+                'x' => builder.ignore_whitespace(true),
+                'q' => &mut builder,
+                flag => return Err(format!("unsupported sh:flags '{}' in shape {}", flag, id)),
+            };
+        }
+        builder
+            .build()
+            .map_err(|e| format!("invalid sh:pattern {} in shape {}: {}", pattern, id, e))
+    }
+
+    /// The predicate paths of the property shapes of a closed shape, and its
+    /// ignored properties.
+    fn closed_predicates(&self, id: NamedOrBlankNodeRef<'_>, shape: &Shape) -> Result<Vec<NamedNode>, String> {
+        let mut allowed: Vec<NamedNode> = shape
+            .properties
+            .iter()
+            .filter_map(|property| match self.shapes[*property].as_ref().and_then(|shape| shape.path.as_ref()) {
+                Some(Path::Predicate(predicate)) => Some(predicate.clone()),
+                _ => None,
+            })
+            .collect();
+        if let Some(ignored) = self.graph.object_for_subject_predicate(id, sh("ignoredProperties").as_ref()) {
+            for predicate in self.list(ignored)? {
+                match predicate {
+                    Term::NamedNode(predicate) => allowed.push(predicate),
+                    predicate => return Err(format!("invalid ignored property {} in shape {}", predicate, id)),
+                }
+            }
+        }
+        Ok(allowed)
+    }
+}
+
+impl Shape {
+    fn focus_nodes(&self, data: &Graph) -> Vec<Term> {
+        let mut focus_nodes = self.target_nodes.clone();
+        for class in self.target_classes.iter() {
+            for class in subclasses(data, class) {
+                focus_nodes.extend(
+                    data.subjects_for_predicate_object(rdf::TYPE, &class)
+                        .map(|subject| subject.into_owned().into()),
+                );
+            }
+        }
+        for predicate in self.target_subjects_of.iter() {
+            focus_nodes.extend(
+                data.triples_for_predicate(predicate)
+                    .map(|triple| triple.subject.into_owned().into()),
+            );
+        }
+        for predicate in self.target_objects_of.iter() {
+            focus_nodes.extend(
+                data.triples_for_predicate(predicate)
+                    .map(|triple| triple.object.into_owned()),
+            );
+        }
+        sorted_unique(focus_nodes)
+    }
+}
+
+/// `nodes` without duplicates, in a stable order.
+fn sorted_unique<T: ToString>(nodes: Vec<T>) -> Vec<T> {
+    let mut seen = HashSet::new();
+    let mut nodes: Vec<T> = nodes.into_iter().filter(|node| seen.insert(node.to_string())).collect();
+    nodes.sort_by_cached_key(|node| node.to_string());
+    nodes
+}
+
+fn as_subject(term: TermRef<'_>) -> Option<NamedOrBlankNodeRef<'_>> {
+    match term {
+        TermRef::NamedNode(node) => Some(node.into()),
+        TermRef::BlankNode(node) => Some(node.into()),
+        _ => None,
+    }
+}
+
+fn boolean(term: TermRef<'_>) -> Option<bool> {
+    match term {
+        TermRef::Literal(literal) => literal.value().parse().ok(),
+        _ => None,
+    }
+}
+
+fn integer(term: TermRef<'_>) -> Option<usize> {
+    match term {
+        TermRef::Literal(literal) => literal.value().parse().ok(),
+        _ => None,
+    }
+}
+
+/// The string a pattern or length constraint applies to, `None` for blank
+/// nodes.
+fn lexical_form(term: &Term) -> Option<&str> {
+    match term {
+        Term::NamedNode(node) => Some(node.as_str()),
+        Term::Literal(literal) => Some(literal.value()),
+        _ => None,
+    }
+}
+
+/// Basic language range matching of RFC 4647.
+fn language_matches(language: &str, range: &str) -> bool {
+    let (language, range) = (language.to_lowercase(), range.to_lowercase());
+    range == "*" || language == range || language.starts_with(&format!("{}-", range))
+}
+
+/// `class` and its transitive subclasses in `data`.
+fn subclasses(data: &Graph, class: &NamedNode) -> Vec<NamedOrBlankNode> {
+    let mut classes = vec![NamedOrBlankNode::from(class.clone())];
+    let mut index = 0;
+    while index < classes.len() {
+        let subclasses: Vec<NamedOrBlankNode> = data
+            .subjects_for_predicate_object(rdfs::SUB_CLASS_OF, &classes[index])
+            .map(|subclass| subclass.into_owned())
+            .filter(|subclass| !classes.contains(subclass))
+            .collect();
+        classes.extend(subclasses);
+        index += 1;
+    }
+    classes
+}
+
+/// Whether `node` is a SHACL instance of `class`, i.e. has a type that is
+/// `class` or a transitive subclass of it.
+fn is_instance(data: &Graph, node: TermRef<'_>, class: &NamedNode) -> bool {
+    let Some(node) = as_subject(node) else {
+        return false;
+    };
+    let mut types: Vec<Term> = data
+        .objects_for_subject_predicate(node, rdf::TYPE)
+        .map(|class| class.into_owned())
+        .collect();
+    let mut index = 0;
+    while index < types.len() {
+        if types[index] == Term::from(class.clone()) {
+            return true;
+        }
+        if let Some(subject) = as_subject(types[index].as_ref()) {
+            let superclasses: Vec<Term> = data
+                .objects_for_subject_predicate(subject, rdfs::SUB_CLASS_OF)
+                .map(|class| class.into_owned())
+                .filter(|class| !types.contains(class))
+                .collect();
+            types.extend(superclasses);
+        }
+        index += 1;
+    }
+    false
+}
+
+fn node_kind_matches(node_kind: &NamedNode, value: TermRef<'_>) -> bool {
+    let (iri, blank_node, literal) = (
+        value.is_named_node(),
+        value.is_blank_node(),
+        value.is_literal(),
+    );
+    match node_kind.as_str().strip_prefix(SH) {
+        Some("IRI") => iri,
+        Some("BlankNode") => blank_node,
+        Some("Literal") => literal,
+        Some("BlankNodeOrIRI") => blank_node || iri,
+        Some("BlankNodeOrLiteral") => blank_node || literal,
+        Some("IRIOrLiteral") => iri || literal,
+        _ => true,
+    }
+}
+
+/// Loads the shapes graph for this postman type from
+/// `{SHACL_SHAPES_DIR}/{type}.ttl`, failing if it can not be parsed or uses
+/// unsupported constructs. Graphs are not validated when there is no such
+/// file, or until the shapes are loaded, so this is called at startup.
+pub fn load_shapes() -> Result<(), Error> {
+    if SHAPES.get().is_some() {
+        return Ok(());
+    }
+    let shapes = match *SHACL_POLICY {
+        ValidationPolicy::Off => None,
+        _ => read_shapes()?,
+    };
+    let _ = SHAPES.set(shapes);
+    Ok(())
+}
+
+fn read_shapes() -> Result<Option<Shapes>, Error> {
+    let path = FsPath::new(SHACL_SHAPES_DIR.as_str()).join(format!("{}.ttl", POSTMAN_TYPE.label()));
+    let raw = match std::fs::read_to_string(&path) {
+        Ok(raw) => raw,
+        Err(e) => {
+            tracing::info!(
+                path = path.to_string_lossy().to_string(),
+                error = e.to_string(),
+                "no shacl shapes, graphs will not be validated"
+            );
+            return Ok(None);
+        }
+    };
+    parse_graph(&raw, RdfFormat::Turtle)
+        .and_then(|graph| Shapes::parse(&graph))
+        .map(Some)
+        .map_err(|e| {
+            Error::Configuration(format!("invalid shacl shapes {}: {}", path.to_string_lossy(), e))
+        })
+}
+
+pub fn validation_enabled() -> bool {
+    SHAPES.get().is_some_and(|shapes| shapes.is_some())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReportMessage<'a> {
+    fdk_id: &'a str,
+    postman_type: &'a str,
+    conforms: bool,
+    results: Vec<ReportMessageResult>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReportMessageResult {
+    focus_node: String,
+    path: Option<String>,
+    value: Option<String>,
+    source_shape: String,
+    constraint: &'static str,
+    severity: Severity,
+    message: String,
+}
+
+/// Validates a graph against the shapes of this postman type, if any are
+/// loaded.
+pub fn validate(graph: &Graph) -> Option<ValidationReport> {
+    match SHAPES.get() {
+        Some(Some(shapes)) => Some(shapes.validate(graph)),
+        _ => None,
+    }
+}
+
+/// Records, logs and publishes the results of validating the graph of
+/// `fdk_id`.
+pub async fn report_results(fdk_id: &str, report: &ValidationReport) {
+    let postman_type = POSTMAN_TYPE.label();
+    SHACL_VALIDATIONS
+        .with_label_values(&[postman_type, &report.conforms().to_string(), SHACL_POLICY.label()])
+        .inc();
+    for result in report.results.iter() {
+        SHACL_VALIDATION_RESULTS
+            .with_label_values(&[postman_type, result.severity.label(), result.constraint])
+            .inc();
+        tracing::warn!(
+            focus_node = result.focus_node.to_string(),
+            path = result.path.as_ref().map(|path| path.to_string()),
+            severity = result.severity.label(),
+            constraint = result.constraint,
+            message = result.message,
+            "shacl validation result"
+        );
+    }
+
+    if let (Some(topic), Some(producer)) = (SHACL_REPORT_TOPIC.as_ref(), REPORT_PRODUCER.as_ref()) {
+        publish_report(producer, topic, fdk_id, report).await;
+    }
+}
+
+/// Applies the configured policy to a validated graph. Fails with
+/// `Error::Validation` when the policy is block and the graph has violations.
+pub fn apply_policy(graph: &mut Graph, fdk_id: &str, report: &ValidationReport) -> Result<(), Error> {
+    match *SHACL_POLICY {
+        ValidationPolicy::Block if report.violations() > 0 => Err(Error::Validation(format!(
+            "{} does not conform to {} shapes: {} violations",
+            fdk_id,
+            POSTMAN_TYPE.label(),
+            report.violations()
+        ))),
+        ValidationPolicy::Annotate if !report.conforms() => {
+            graph.extend(report.to_graph().iter());
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+async fn publish_report(
+    producer: &FutureProducer,
+    topic: &str,
+    fdk_id: &str,
+    report: &ValidationReport,
+) {
+    let message = ReportMessage {
+        fdk_id,
+        postman_type: POSTMAN_TYPE.label(),
+        conforms: report.conforms(),
+        results: report
+            .results
+            .iter()
+            .map(|result| ReportMessageResult {
+                focus_node: result.focus_node.to_string(),
+                path: result.path.as_ref().map(|path| path.to_string()),
+                value: result.value.as_ref().map(|value| value.to_string()),
+                source_shape: result.source_shape.to_string(),
+                constraint: result.constraint,
+                severity: result.severity,
+                message: result.message.clone(),
+            })
+            .collect(),
+    };
+    let result = match serde_json::to_vec(&message) {
+        Ok(payload) => produce(producer, topic, fdk_id, &payload).await,
+        Err(e) => Err(Error::Encode(e.to_string())),
+    };
+    if let Err(e) = result {
+        tracing::warn!(error = e.to_string(), topic, "unable to publish shacl report");
+    }
+}
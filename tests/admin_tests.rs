@@ -0,0 +1,105 @@
+mod common;
+
+use actix_web::{
+    http::StatusCode,
+    test::{call_service, init_service, read_body_json, TestRequest},
+    web, App,
+};
+use fdk_rdf_postman::{
+    admin::{get_replay_status, start_replay, start_reprocess},
+    diff_store::create_http_client,
+    kafka::{key_partition, MessageHandler},
+    reconcile::LatestEvents,
+    schemas::HarvestEventType,
+    source::{InMemorySource, MessageSource},
+};
+
+use common::{decoder, event_at, GRAPH};
+
+const API_KEY: &str = "admin-api-key";
+
+#[actix_web::test]
+async fn rejects_requests_without_the_admin_api_key() {
+    std::env::set_var("ADMIN_API_KEY", API_KEY);
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(create_http_client().unwrap()))
+            .service(start_replay)
+            .service(start_reprocess)
+            .service(get_replay_status),
+    )
+    .await;
+
+    let requests = [
+        TestRequest::get().uri("/admin/replay/status"),
+        TestRequest::get()
+            .uri("/admin/replay/status")
+            .insert_header(("X-API-KEY", "wrong-key")),
+        TestRequest::post()
+            .uri("/admin/replay")
+            .set_json(serde_json::json!({ "start": "beginning" })),
+        TestRequest::post().uri("/admin/reprocess/fdk-id"),
+    ];
+    for request in requests {
+        let response = call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[actix_web::test]
+async fn reports_the_replay_status() {
+    std::env::set_var("ADMIN_API_KEY", API_KEY);
+    let app = init_service(App::new().service(get_replay_status)).await;
+
+    let request = TestRequest::get()
+        .uri("/admin/replay/status")
+        .insert_header(("X-API-KEY", API_KEY))
+        .to_request();
+    let response = call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let status: serde_json::Value = read_body_json(response).await;
+    assert_eq!(status["state"], "idle");
+    assert_eq!(status["processed"], 0);
+}
+
+#[test]
+fn targets_the_partition_of_the_java_default_partitioner() {
+    // Hashes from the murmur2 tests of the Java Kafka client.
+    let hashes: [(&str, i32); 6] = [
+        ("21", -973932308),
+        ("foobar", -790332482),
+        ("a-little-bit-long-string", -985981536),
+        ("a-little-bit-longer-string", -1486304829),
+        ("lkjh234lh9fiuh90y23oiuhsafujhadof229phr9h19h89h8", -58897971),
+        ("abc", 479470107),
+    ];
+    for (key, hash) in hashes {
+        let positive = hash & 0x7fffffff;
+        assert_eq!(key_partition(key.as_bytes(), 12), positive % 12, "{}", key);
+        assert_eq!(key_partition(key.as_bytes(), i32::MAX), positive % i32::MAX, "{}", key);
+    }
+}
+
+#[tokio::test]
+async fn reprocesses_the_latest_actionable_event_of_the_fdk_id() {
+    let source = InMemorySource::default();
+    for event in [
+        event_at(HarvestEventType::DatasetReasoned, "fdk-id", GRAPH, 1),
+        event_at(HarvestEventType::DatasetReasoned, "other-fdk-id", GRAPH, 3),
+        event_at(HarvestEventType::DatasetReasoned, "fdk-id", GRAPH, 2),
+        // Harvested events are not applied to the diff store, so they must not
+        // hide the newest reasoned event.
+        event_at(HarvestEventType::DatasetHarvested, "fdk-id", GRAPH, 3),
+    ] {
+        source.push_event(&event).unwrap();
+    }
+
+    let mut latest = LatestEvents::with_decoder(decoder(), Some("fdk-id".to_string()));
+    while let Some(message) = source.recv().await.unwrap() {
+        latest.handle(&message).await.unwrap();
+    }
+
+    assert_eq!(latest.events.len(), 1);
+    assert_eq!((latest.events["fdk-id"].offset, latest.events["fdk-id"].timestamp), (2, 2));
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use std::time::Duration;
+
+use fdk_rdf_postman::{
+    diff_store::{graph_to_store, stored_graph_format, update_diff_store},
+    mock_diff_store::MockDiffStore,
+    rdf::{isomorphic, parse_graph},
+    validation::load_shapes,
+};
+
+use common::reasoned;
+
+const SHAPES: &str = r#"
+@prefix sh: <http://www.w3.org/ns/shacl#> .
+@prefix dct: <http://purl.org/dc/terms/> .
+@prefix dcat: <http://www.w3.org/ns/dcat#> .
+
+<https://example.org/shapes/Dataset> a sh:NodeShape ;
+    sh:targetClass dcat:Dataset ;
+    sh:property [ sh:path dct:title ; sh:minCount 1 ] .
+"#;
+
+const DATASET: &str = "<https://example.org/datasets/1> a <http://www.w3.org/ns/dcat#Dataset> .";
+
+#[tokio::test]
+async fn annotated_graph_matches_graph_to_store() {
+    let shapes_dir = std::env::temp_dir().join("fdk-rdf-postman-annotate-test");
+    std::fs::create_dir_all(&shapes_dir).unwrap();
+    std::fs::write(shapes_dir.join("dataset.ttl"), SHAPES).unwrap();
+    std::env::set_var("SHACL_SHAPES_DIR", &shapes_dir);
+    std::env::set_var("SHACL_POLICY", "annotate");
+    std::env::set_var("POSTMAN_TYPE", "dataset");
+    load_shapes().unwrap();
+
+    let diff_store = MockDiffStore::start().unwrap();
+    std::env::set_var("DIFF_STORE_URL", diff_store.url());
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+
+    update_diff_store(reasoned("annotated", DATASET), &http_client).await.unwrap();
+
+    let stored = diff_store.graph("annotated").unwrap().graph.unwrap();
+    let stored = parse_graph(&stored, stored_graph_format()).unwrap();
+    let expected = graph_to_store(&reasoned("annotated", DATASET)).await.unwrap();
+    assert!(stored.len() > 1, "validation report not added to the graph");
+    assert!(isomorphic(&expected, &stored));
+}
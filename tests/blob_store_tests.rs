@@ -0,0 +1,29 @@
+use actix_web::http::StatusCode;
+use fdk_rdf_postman::{
+    blob_store::{BlobStore, HttpBlobStore},
+    error::Error,
+    mock_diff_store::MockDiffStore,
+};
+
+#[tokio::test]
+async fn stores_payload_and_returns_its_url() {
+    let mock = MockDiffStore::start().unwrap();
+    let store = HttpBlobStore::new(&mock.blob_store_url(), None, reqwest::Client::new());
+
+    let reference = store.put("dataset/1", b"<a> <b> <c> .", "text/turtle").await.unwrap();
+    assert!(reference.starts_with(&format!("{}/dataset_1-", mock.blob_store_url())));
+    assert_eq!(mock.blob(&reference).unwrap(), b"<a> <b> <c> .");
+    assert_eq!(
+        reqwest::get(&reference).await.unwrap().bytes().await.unwrap().as_ref(),
+        b"<a> <b> <c> ."
+    );
+
+    let same = store.put("dataset/1", b"<a> <b> <c> .", "text/turtle").await.unwrap();
+    assert_eq!(reference, same);
+
+    mock.fail_next(StatusCode::FORBIDDEN, 1);
+    match store.put("dataset/1", b"<a> <b> <c> .", "text/turtle").await {
+        Err(Error::SinkStatus { status, .. }) => assert_eq!(status, StatusCode::FORBIDDEN),
+        result => panic!("unexpected result {:?}", result),
+    }
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use fdk_rdf_postman::{
+    coalesce::run_coalescing_processor,
+    diff_store::create_http_client,
+    mock_diff_store::MockDiffStore,
+    output::Output,
+    schemas::HarvestEventType,
+    source::InMemorySource,
+};
+
+use common::{decoder, event_at};
+
+#[tokio::test]
+async fn applies_newest_event_per_fdk_id() {
+    let diff_store = MockDiffStore::start().unwrap();
+    std::env::set_var("DIFF_STORE_URL", diff_store.url());
+    diff_store.insert("a", "<a> <b> <c> .");
+    diff_store.insert("b", "<a> <b> <c> .");
+    diff_store.fail_next(StatusCode::SERVICE_UNAVAILABLE, 1);
+
+    let graph = "<https://example.org/datasets/a> <http://purl.org/dc/terms/title> \"A\" .";
+    let source = InMemorySource::default();
+    let stale = "<x> <y> <z> .";
+    source.push_event(&event_at(HarvestEventType::DatasetReasoned, "a", stale, 1)).unwrap();
+    source.push_event(&event_at(HarvestEventType::DatasetRemoved, "a", "", 2)).unwrap();
+    source.push_event(&event_at(HarvestEventType::DatasetReasoned, "b", graph, 1)).unwrap();
+    source.push_event(&event_at(HarvestEventType::DatasetHarvested, "c", graph, 1)).unwrap();
+    source.push_event(&event_at(HarvestEventType::DatasetRemoved, "b", "", 2)).unwrap();
+    source.push_event(&event_at(HarvestEventType::DatasetReasoned, "a", graph, 3)).unwrap();
+
+    let mut decoder = decoder();
+    let http_client = create_http_client().unwrap();
+
+    run_coalescing_processor(
+        0,
+        &source,
+        &mut decoder,
+        &http_client,
+        &Output::default(),
+        Duration::from_millis(100),
+    )
+    .await
+    .unwrap();
+
+    // The delete of "b" fails once, so the messages from the first one of "b"
+    // are consumed again, after those of "a" before it were acknowledged.
+    assert_eq!(source.acked(), vec![0, 1, 2, 3, 4, 5]);
+    let requests: Vec<_> = diff_store
+        .requests()
+        .into_iter()
+        .map(|request| (request.method, request.id.unwrap(), request.status))
+        .collect();
+    assert_eq!(
+        requests,
+        vec![
+            ("DELETE".to_string(), "b".to_string(), 503),
+            ("POST".to_string(), "a".to_string(), 200),
+            ("DELETE".to_string(), "b".to_string(), 200),
+            ("POST".to_string(), "a".to_string(), 200),
+        ]
+    );
+    diff_store.assert_stored("a", graph);
+    diff_store.assert_absent("b");
+}
@@ -0,0 +1,71 @@
+//! Fixtures shared by the test files. Each test file is its own crate, and
+//! uses only some of them.
+#![allow(dead_code)]
+
+use std::{sync::OnceLock, time::Duration};
+
+use fdk_rdf_postman::{
+    mock_diff_store::MockDiffStore,
+    schema_cache::CachingAvroDecoder,
+    schemas::{HarvestEvent, HarvestEventType},
+};
+use schema_registry_converter::async_impl::schema_registry::SrSettings;
+
+pub const GRAPH: &str =
+    "<https://example.org/datasets/1> <http://purl.org/dc/terms/title> \"Title\" .";
+pub const TIMESTAMP: i64 = 1647698566000;
+
+/// Mock diff store shared by all tests of a file, since the diff store URL is
+/// read once per process. It runs on its own runtime, so that it outlives the
+/// runtime of the test that started it. Tests use distinct fdkIds, and inject
+/// faults only for their own graphs.
+pub fn diff_store() -> &'static MockDiffStore {
+    static DIFF_STORE: OnceLock<MockDiffStore> = OnceLock::new();
+    DIFF_STORE.get_or_init(|| {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                sender.send(MockDiffStore::start().unwrap()).unwrap();
+                std::future::pending::<()>().await
+            })
+        });
+        let diff_store = receiver.recv().unwrap();
+        std::env::set_var("DIFF_STORE_URL", diff_store.url());
+        diff_store
+    })
+}
+
+/// Decoder for messages pushed with `InMemorySource::push_event`. Nothing
+/// listens on port 1, so messages are decoded with the seed schema, which
+/// events are encoded with under id 1.
+pub fn decoder() -> CachingAvroDecoder<'static> {
+    let sr_settings = SrSettings::new_builder("http://127.0.0.1:1".to_string())
+        .set_timeout(Duration::from_millis(500))
+        .build()
+        .unwrap();
+    let cache_dir = std::env::temp_dir().join("fdk-rdf-postman-test-schema-cache");
+    CachingAvroDecoder::with_cache_dir(sr_settings, cache_dir).with_seed_schema_ids([1])
+}
+
+pub fn reasoned(fdk_id: &str, graph: &str) -> HarvestEvent {
+    event(HarvestEventType::DatasetReasoned, fdk_id, graph)
+}
+
+pub fn event(event_type: HarvestEventType, fdk_id: &str, graph: &str) -> HarvestEvent {
+    event_at(event_type, fdk_id, graph, TIMESTAMP)
+}
+
+pub fn event_at(
+    event_type: HarvestEventType,
+    fdk_id: &str,
+    graph: &str,
+    timestamp: i64,
+) -> HarvestEvent {
+    HarvestEvent {
+        event_type,
+        fdk_id: fdk_id.to_string(),
+        graph: graph.to_string(),
+        timestamp,
+    }
+}
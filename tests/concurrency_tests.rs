@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use fdk_rdf_postman::concurrency::{
+    check_adaptive_concurrency, AdaptiveLimitConfig, AdaptiveLimiter, Outcome,
+};
+use reqwest::StatusCode;
+
+fn limiter(initial_limit: f64) -> AdaptiveLimiter {
+    AdaptiveLimiter::new(AdaptiveLimitConfig {
+        initial_limit,
+        max_limit: 8.0,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn increases_while_healthy_and_cuts_on_overload() {
+    let limiter = limiter(2.0);
+    for _ in 0..6 {
+        limiter.record(Outcome::Success(Duration::from_millis(10)));
+    }
+    assert_eq!(limiter.limit(), 4);
+
+    limiter.record(Outcome::Overload);
+    assert_eq!(limiter.limit(), 2);
+
+    for _ in 0..100 {
+        limiter.record(Outcome::Success(Duration::from_millis(10)));
+    }
+    assert_eq!(limiter.limit(), 8);
+}
+
+#[test]
+fn cuts_when_latency_climbs() {
+    let limiter = limiter(4.0);
+    limiter.record(Outcome::Success(Duration::from_millis(10)));
+    limiter.record(Outcome::Success(Duration::from_millis(15)));
+    assert_eq!(limiter.limit(), 4);
+
+    limiter.record(Outcome::Success(Duration::from_millis(50)));
+    assert_eq!(limiter.limit(), 2);
+}
+
+#[tokio::test]
+async fn waits_for_a_free_slot() {
+    let limiter = limiter(1.0);
+    let permit = limiter.acquire().await;
+    let blocked = tokio::time::timeout(Duration::from_millis(50), limiter.acquire()).await;
+    assert!(blocked.is_err());
+
+    drop(permit);
+    assert_eq!(limiter.in_flight(), 0);
+    let acquired = tokio::time::timeout(Duration::from_millis(50), limiter.acquire()).await;
+    assert!(acquired.is_ok());
+}
+
+#[test]
+fn ignores_server_errors_other_than_overload() {
+    let latency = Duration::from_millis(1);
+    assert_eq!(
+        Outcome::from_status(StatusCode::INTERNAL_SERVER_ERROR, latency),
+        Outcome::Ignore
+    );
+    assert_eq!(Outcome::from_status(StatusCode::BAD_GATEWAY, latency), Outcome::Ignore);
+    assert_eq!(
+        Outcome::from_status(StatusCode::SERVICE_UNAVAILABLE, latency),
+        Outcome::Overload
+    );
+    assert_eq!(
+        Outcome::from_status(StatusCode::TOO_MANY_REQUESTS, latency),
+        Outcome::Overload
+    );
+    assert_eq!(Outcome::from_status(StatusCode::OK, latency), Outcome::Success(latency));
+    assert_eq!(
+        Outcome::from_status(StatusCode::BAD_REQUEST, latency),
+        Outcome::Success(latency)
+    );
+
+    // A fast failure does not lower the baseline, so the latency of healthy
+    // requests is still tolerated afterwards.
+    let limiter = limiter(4.0);
+    limiter.record(Outcome::Success(Duration::from_millis(10)));
+    limiter.record(Outcome::from_status(StatusCode::INTERNAL_SERVER_ERROR, latency));
+    limiter.record(Outcome::Success(Duration::from_millis(12)));
+    assert_eq!(limiter.limit(), 4);
+}
+
+#[test]
+fn rejects_a_minimum_above_the_worker_count() {
+    std::env::set_var("DIFF_STORE_ADAPTIVE_CONCURRENCY", "true");
+    std::env::set_var("DIFF_STORE_CONCURRENCY_MIN", "8");
+    std::env::set_var("WORKERS", "4");
+
+    assert!(check_adaptive_concurrency().is_err());
+}
+
+#[test]
+fn rejects_backoff_factors_outside_the_unit_interval() {
+    for backoff in [0.0, 1.0, 1.5] {
+        let config = AdaptiveLimitConfig {
+            backoff,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err(), "{}", backoff);
+    }
+    assert!(AdaptiveLimitConfig::default().validate().is_ok());
+}
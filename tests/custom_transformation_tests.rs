@@ -0,0 +1,49 @@
+use fdk_rdf_postman::{
+    diff_store::graph_to_store,
+    error::Error,
+    schemas::{HarvestEvent, HarvestEventType},
+    transform::{install_transformations, GraphTransformation, TransformationPipeline},
+};
+use oxrdf::{Graph, Literal, NamedNode, Triple};
+
+/// Adds the fdkId of the event to its graph.
+struct TagFdkId;
+
+impl GraphTransformation for TagFdkId {
+    fn name(&self) -> &str {
+        "tag_fdk_id"
+    }
+
+    fn apply(&self, mut graph: Graph, event: &HarvestEvent) -> Result<Graph, Error> {
+        graph.insert(&Triple::new(
+            NamedNode::new_unchecked(format!("https://example.org/{}", event.fdk_id)),
+            NamedNode::new_unchecked("http://purl.org/dc/terms/identifier"),
+            Literal::new_simple_literal(&event.fdk_id),
+        ));
+        Ok(graph)
+    }
+}
+
+#[tokio::test]
+async fn applies_installed_custom_transformations() {
+    install_transformations(TransformationPipeline::from_env().unwrap().with(Box::new(TagFdkId)))
+        .unwrap();
+    let event = HarvestEvent {
+        event_type: HarvestEventType::DatasetReasoned,
+        fdk_id: "tagged".to_string(),
+        graph: "<https://example.org/datasets/1> <http://purl.org/dc/terms/title> \"Title\" ."
+            .to_string(),
+        timestamp: 1647698566000,
+    };
+
+    let graph = graph_to_store(&event).await.unwrap();
+    assert_eq!(graph.len(), 2);
+    assert!(graph.contains(&Triple::new(
+        NamedNode::new_unchecked("https://example.org/tagged"),
+        NamedNode::new_unchecked("http://purl.org/dc/terms/identifier"),
+        Literal::new_simple_literal("tagged"),
+    )));
+
+    // The pipeline in use can not be replaced.
+    assert!(install_transformations(TransformationPipeline::new()).is_err());
+}
@@ -0,0 +1,24 @@
+use fdk_rdf_postman::diff_store::SuccessStatuses;
+use reqwest::StatusCode;
+
+#[test]
+fn parses_success_statuses() {
+    let statuses = SuccessStatuses::parse("2xx, 404").unwrap();
+    assert!(statuses.contains(StatusCode::OK));
+    assert!(statuses.contains(StatusCode::ACCEPTED));
+    assert!(statuses.contains(StatusCode::NO_CONTENT));
+    assert!(statuses.contains(StatusCode::NOT_FOUND));
+    assert!(!statuses.contains(StatusCode::CONFLICT));
+    assert!(!statuses.contains(StatusCode::INTERNAL_SERVER_ERROR));
+
+    let statuses = SuccessStatuses::parse("200").unwrap();
+    assert!(statuses.contains(StatusCode::OK));
+    assert!(!statuses.contains(StatusCode::CREATED));
+}
+
+#[test]
+fn rejects_invalid_success_statuses() {
+    assert!(SuccessStatuses::parse("6xx").is_err());
+    assert!(SuccessStatuses::parse("ok").is_err());
+    assert!(SuccessStatuses::parse("2000").is_err());
+}
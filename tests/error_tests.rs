@@ -0,0 +1,71 @@
+use fdk_rdf_postman::error::{Error, Retryability};
+use reqwest::StatusCode;
+
+fn sink_status(status: StatusCode) -> Error {
+    Error::SinkStatus {
+        message: "Invalid response from diff store".to_string(),
+        status,
+        body: String::new(),
+    }
+}
+
+#[test]
+fn classifies_sink_status_by_code() {
+    assert!(sink_status(StatusCode::INTERNAL_SERVER_ERROR).is_retryable());
+    assert!(sink_status(StatusCode::SERVICE_UNAVAILABLE).is_retryable());
+    assert!(sink_status(StatusCode::TOO_MANY_REQUESTS).is_retryable());
+    assert!(!sink_status(StatusCode::NOT_FOUND).is_retryable());
+    assert!(!sink_status(StatusCode::BAD_REQUEST).is_retryable());
+    assert_eq!(sink_status(StatusCode::NOT_FOUND).class(), "http_404");
+}
+
+#[test]
+fn groups_uncommon_sink_statuses_by_class() {
+    assert_eq!(sink_status(StatusCode::SERVICE_UNAVAILABLE).class(), "http_503");
+    assert_eq!(sink_status(StatusCode::IM_A_TEAPOT).class(), "http_4xx");
+    assert_eq!(sink_status(StatusCode::HTTP_VERSION_NOT_SUPPORTED).class(), "http_5xx");
+    assert_eq!(sink_status(StatusCode::MOVED_PERMANENTLY).class(), "http_other");
+}
+
+#[test]
+fn classifies_decode_and_timeout_errors() {
+    assert_eq!(
+        Error::Decode("bad bytes".to_string()).retryability(),
+        Retryability::Permanent
+    );
+    assert_eq!(
+        Error::UnknownSchema("42".to_string()).retryability(),
+        Retryability::Permanent
+    );
+    assert_eq!(
+        Error::Validation("too large".to_string()).retryability(),
+        Retryability::Permanent
+    );
+    assert_eq!(
+        Error::Timeout("diff store".to_string()).retryability(),
+        Retryability::Retryable
+    );
+    assert_eq!(Error::Timeout("diff store".to_string()).class(), "timeout");
+}
+
+#[test]
+fn retries_unavailable_resources_only() {
+    assert_eq!(
+        Error::Unavailable("consumer group metadata".to_string()).retryability(),
+        Retryability::Retryable
+    );
+    assert_eq!(Error::Unavailable("metadata".to_string()).class(), "unavailable");
+    assert_eq!(
+        Error::NotFound("event for fdk-id".to_string()).retryability(),
+        Retryability::Permanent
+    );
+    assert_eq!(Error::NotFound("event".to_string()).class(), "not_found");
+    assert_eq!(
+        Error::Configuration("invalid status".to_string()).retryability(),
+        Retryability::Permanent
+    );
+    assert_eq!(
+        Error::Encode("applied event".to_string()).retryability(),
+        Retryability::Permanent
+    );
+}
@@ -0,0 +1,43 @@
+use fdk_rdf_postman::{
+    diff_store::{create_http_client, update_diff_store},
+    mock_diff_store::{MockDiffStore, RecordedRequest},
+    rdf::{isomorphic, parse_graph},
+    schemas::{HarvestEvent, HarvestEventType},
+};
+use oxrdfio::RdfFormat;
+
+const TURTLE: &str = r#"
+@prefix dct: <http://purl.org/dc/terms/> .
+<https://example.org/datasets/1> dct:title "Title" .
+"#;
+
+#[tokio::test]
+async fn posts_converted_graphs_in_the_graph_field() {
+    std::env::set_var("DIFF_STORE_GRAPH_FORMAT", "n-triples");
+    let diff_store = MockDiffStore::start().unwrap();
+    std::env::set_var("DIFF_STORE_URL", diff_store.url());
+    let event = HarvestEvent {
+        event_type: HarvestEventType::DatasetReasoned,
+        fdk_id: "converted".to_string(),
+        graph: TURTLE.to_string(),
+        timestamp: 1647698566000,
+    };
+
+    update_diff_store(event, &create_http_client().unwrap()).await.unwrap();
+
+    let stored = diff_store.graph("converted").unwrap();
+    let graph = parse_graph(&stored.graph.unwrap(), RdfFormat::NTriples).unwrap();
+    assert!(isomorphic(&graph, &parse_graph(TURTLE, RdfFormat::Turtle).unwrap()));
+    assert_eq!(
+        diff_store.requests(),
+        vec![RecordedRequest {
+            method: "POST".to_string(),
+            path: "/api/graphs".to_string(),
+            id: Some("converted".to_string()),
+            idempotency_key: Some("converted-1647698566000".to_string()),
+            traceparent: None,
+            status: 200,
+        }]
+    );
+    diff_store.stop().await;
+}
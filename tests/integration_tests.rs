@@ -1,87 +1,32 @@
-use std::{
-    fmt,
-    net::{IpAddr, Ipv4Addr, SocketAddr}
-};
+use std::net::TcpListener;
 
 use fdk_rdf_postman::{
     kafka::{create_consumer, INPUT_TOPIC},
+    mock_diff_store::MockDiffStore,
     schemas::{HarvestEvent, HarvestEventType},
 };
-use httptest::{
-    matchers::{all_of, json_decoded, request, ExecutionContext, Matcher},
-    responders::status_code,
-    Expectation, Server, ServerBuilder,
-};
 use kafka_utils::{consume_all_messages, process_single_message, TestProducer};
-use serde::{Deserialize, Serialize};
 
 mod kafka_utils;
 
 #[tokio::test]
 async fn test() {
-    let mut server = ServerBuilder::new()
-        .bind_addr(SocketAddr::new(
-            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
-            8090,
-        ))
-        .run()
+    let diff_store = MockDiffStore::start_on(TcpListener::bind(("127.0.0.1", 8090)).unwrap())
         .unwrap();
 
-    assert_transformation(
-        &server,
-        "fdk-id",
-        "\
+    let graph = "\
         @prefix si: <https://www.w3schools.com/rdf/> .
         <https://digdir.no/dataset/007> si:author \"James Bond\" ;
             si:title \"The man!\" .
-        ",
-    ).await;
-
-    assert_delete(&server, "fdk-id").await;
-
-    // Assert that the diff store received expected requests.
-    server.verify_and_clear();
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DiffStoreGraph {
-    pub id: String,
-    pub graph: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct DiffStoreID {
-    pub id: String,
-}
-
-impl Matcher<DiffStoreGraph> for DiffStoreGraph {
-    fn matches(&mut self, expected: &DiffStoreGraph, _ctx: &mut ExecutionContext) -> bool {
-        assert_eq!(expected.id, self.id);
-        assert_eq!(expected.graph, self.graph);
-        true
-    }
+        ";
+    assert_transformation("fdk-id", graph).await;
+    diff_store.assert_stored("fdk-id", graph);
 
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        <Self as fmt::Debug>::fmt(self, f)
-    }
+    assert_delete("fdk-id").await;
+    diff_store.assert_absent("fdk-id");
 }
 
-impl Matcher<DiffStoreID> for DiffStoreID {
-    fn matches(&mut self, expected: &DiffStoreID, _ctx: &mut ExecutionContext) -> bool {
-        assert_eq!(expected.id, self.id);
-        true
-    }
-
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        <Self as fmt::Debug>::fmt(self, f)
-    }
-}
-
-async fn assert_transformation(
-    server: &Server,
-    id: &str,
-    input: &str,
-) {
+async fn assert_transformation(id: &str, input: &str) {
     let consumer = create_consumer().unwrap();
     // Clear topic of all existing messages.
     consume_all_messages(&consumer).await.unwrap();
@@ -96,20 +41,6 @@ async fn assert_transformation(
         graph: input.to_string(),
     };
 
-    let expected_body = DiffStoreGraph {
-        id: id.to_string(),
-        graph: input.to_string(),
-    };
-
-    server.expect(
-        Expectation::matching(all_of![
-            request::method("POST"),
-            request::path("/api/graphs"),
-            request::body(json_decoded::<DiffStoreGraph, DiffStoreGraph>(expected_body)),
-        ])
-            .respond_with(status_code(200)),
-    );
-
     // Produce message to topic.
     TestProducer::new(&INPUT_TOPIC)
         .produce(&input_message, "no.fdk.dataset.DatasetEvent")
@@ -119,7 +50,7 @@ async fn assert_transformation(
     processor.await.unwrap();
 }
 
-async fn assert_delete(server: &Server, id: &str) {
+async fn assert_delete(id: &str) {
     let consumer = create_consumer().unwrap();
     // Clear topic of all existing messages.
     consume_all_messages(&consumer).await.unwrap();
@@ -134,17 +65,6 @@ async fn assert_delete(server: &Server, id: &str) {
         graph: "".to_string(),
     };
 
-    let expected_body = DiffStoreID { id: id.to_string() };
-
-    server.expect(
-        Expectation::matching(all_of![
-            request::method("DELETE"),
-            request::path("/api/graphs"),
-            request::body(json_decoded::<DiffStoreID, DiffStoreID>(expected_body)),
-        ])
-            .respond_with(status_code(200)),
-    );
-
     // Produce message to topic.
     TestProducer::new(&INPUT_TOPIC)
         .produce(&input_message, "no.fdk.dataset.DatasetEvent")
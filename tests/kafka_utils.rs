@@ -1,11 +1,13 @@
 use std::time::Duration;
 
 use fdk_rdf_postman::{
+    diff_store::create_http_client,
     error::Error,
-    kafka::{handle_message, BROKERS},
+    kafka::{handle_message, PostmanConsumer, BROKERS},
+    schema_cache::CachingAvroDecoder,
 };
 use rdkafka::{
-    consumer::{CommitMode, Consumer, StreamConsumer},
+    consumer::{CommitMode, Consumer},
     error::KafkaError,
     message::BorrowedMessage,
     producer::{FutureProducer, FutureRecord},
@@ -13,7 +15,7 @@ use rdkafka::{
 };
 use schema_registry_converter::{
     async_impl::{
-        avro::{AvroDecoder, AvroEncoder},
+        avro::AvroEncoder,
         schema_registry::SrSettings,
     },
     schema_registry_common::SubjectNameStrategy,
@@ -21,11 +23,11 @@ use schema_registry_converter::{
 use serde::Serialize;
 
 /// Consumes all messages and drops their content.
-pub async fn consume_all_messages(consumer: &StreamConsumer) -> Result<(), KafkaError> {
+pub async fn consume_all_messages(consumer: &PostmanConsumer) -> Result<(), KafkaError> {
     loop {
         // Loop untill no nessage can be received within timeout.
         let timeout_duration = Duration::from_millis(500);
-        if let None = consume_single_message(consumer, timeout_duration).await? {
+        if consume_single_message(consumer, timeout_duration).await?.is_none() {
             return Ok(());
         }
     }
@@ -33,9 +35,9 @@ pub async fn consume_all_messages(consumer: &StreamConsumer) -> Result<(), Kafka
 
 /// Consumes and returns a single message, if received within the timeout period.
 pub async fn consume_single_message(
-    consumer: &StreamConsumer,
+    consumer: &PostmanConsumer,
     timeout_duration: Duration,
-) -> Result<Option<BorrowedMessage>, KafkaError> {
+) -> Result<Option<BorrowedMessage<'_>>, KafkaError> {
     match tokio::time::timeout(timeout_duration, consumer.recv()).await {
         Ok(result) => {
             let message = result?;
@@ -48,9 +50,9 @@ pub async fn consume_single_message(
     }
 }
 
-pub async fn process_single_message(consumer: StreamConsumer) -> Result<(), Error> {
-    let mut decoder = AvroDecoder::new(sr_settings());
-    let http_client = reqwest::Client::new();
+pub async fn process_single_message(consumer: PostmanConsumer) -> Result<(), Error> {
+    let mut decoder = CachingAvroDecoder::new(sr_settings());
+    let http_client = create_http_client().unwrap();
 
     let timeout_duration = Duration::from_millis(3000);
     let message = consume_single_message(&consumer, timeout_duration)
@@ -0,0 +1,160 @@
+mod common;
+
+use std::{collections::HashMap, time::Duration};
+
+use fdk_rdf_postman::{
+    diff_store::{create_http_client, update_diff_store},
+    error::Error,
+    metrics::{
+        record_consumer_statistics, record_rebalance, ASSIGNED_PARTITIONS,
+        CONSUMER_COMMITTED_OFFSET, CONSUMER_HIGH_WATERMARK, CONSUMER_LAG,
+        DIFF_STORE_CONNECTION_ERRORS, DIFF_STORE_REQUEST_DURATION, DIFF_STORE_REQUEST_SIZE,
+        DIFF_STORE_RESPONSE_SIZE, DIFF_STORE_TIMEOUTS, REBALANCES,
+    },
+    mock_diff_store::MockDiffStore,
+};
+use prometheus::core::Collector;
+use rdkafka::{
+    consumer::Rebalance,
+    error::KafkaError,
+    statistics::{Partition, Topic},
+    Statistics, TopicPartitionList,
+};
+
+use common::{reasoned, GRAPH};
+
+const TOPIC: &str = "dataset-events";
+
+fn partition(partition: i32, desired: bool, committed_offset: i64, hi_offset: i64) -> Partition {
+    Partition {
+        partition,
+        desired,
+        committed_offset,
+        hi_offset,
+        consumer_lag: hi_offset - committed_offset,
+        ..Default::default()
+    }
+}
+
+fn statistics(partitions: Vec<Partition>) -> Statistics {
+    let topic = Topic {
+        topic: TOPIC.to_string(),
+        partitions: partitions
+            .into_iter()
+            .map(|partition| (partition.partition, partition))
+            .collect(),
+        ..Default::default()
+    };
+    Statistics {
+        topics: HashMap::from([(TOPIC.to_string(), topic)]),
+        ..Default::default()
+    }
+}
+
+fn partitions(partitions: &[i32]) -> TopicPartitionList {
+    let mut list = TopicPartitionList::new();
+    for partition in partitions {
+        list.add_partition(TOPIC, *partition);
+    }
+    list
+}
+
+/// Whether a lag is exported for `partition`, without creating one as
+/// `with_label_values` would.
+fn has_partition_metrics(partition: &str, group: &str) -> bool {
+    CONSUMER_LAG.collect().iter().flat_map(|family| family.get_metric()).any(|metric| {
+        let labels: HashMap<_, _> = metric
+            .get_label()
+            .iter()
+            .map(|label| (label.get_name(), label.get_value()))
+            .collect();
+        labels.get("partition") == Some(&partition)
+            && labels.get("consumer_group") == Some(&group)
+    })
+}
+
+#[test]
+fn records_offsets_of_assigned_partitions() {
+    let group = "statistics-group";
+    record_consumer_statistics(
+        &statistics(vec![
+            partition(0, true, 40, 100),
+            partition(1, false, 10, 20),
+            partition(-1, true, 0, 5),
+        ]),
+        group,
+    );
+
+    let labels = [TOPIC, "0", group];
+    assert_eq!(CONSUMER_COMMITTED_OFFSET.with_label_values(&labels).get(), 40);
+    assert_eq!(CONSUMER_HIGH_WATERMARK.with_label_values(&labels).get(), 100);
+    assert_eq!(CONSUMER_LAG.with_label_values(&labels).get(), 60);
+    assert!(!has_partition_metrics("1", group));
+    assert!(!has_partition_metrics("-1", group));
+}
+
+#[test]
+fn records_rebalances_and_drops_metrics_of_revoked_partitions() {
+    let group = "rebalance-group";
+    let assigned = partitions(&[0, 1]);
+    let revoked = partitions(&[1]);
+
+    record_rebalance(&Rebalance::Assign(&assigned), group);
+    record_consumer_statistics(
+        &statistics(vec![partition(0, true, 1, 2), partition(1, true, 1, 3)]),
+        group,
+    );
+    assert_eq!(ASSIGNED_PARTITIONS.with_label_values(&[TOPIC, group]).get(), 2);
+    assert!(has_partition_metrics("1", group));
+
+    record_rebalance(&Rebalance::Revoke(&revoked), group);
+    assert_eq!(ASSIGNED_PARTITIONS.with_label_values(&[TOPIC, group]).get(), 1);
+    assert!(has_partition_metrics("0", group));
+    assert!(!has_partition_metrics("1", group));
+
+    record_rebalance(&Rebalance::Error(KafkaError::Canceled), group);
+    assert_eq!(REBALANCES.with_label_values(&[TOPIC, group, "assign"]).get(), 1);
+    assert_eq!(REBALANCES.with_label_values(&[TOPIC, group, "revoke"]).get(), 1);
+    assert_eq!(REBALANCES.with_label_values(&["", group, "error"]).get(), 1);
+}
+
+/// Number of POST requests observed by the request duration histogram with
+/// the status label `status`.
+fn post_durations(status: &str) -> u64 {
+    DIFF_STORE_REQUEST_DURATION.with_label_values(&["POST", status]).get_sample_count()
+}
+
+#[tokio::test]
+async fn records_diff_store_request_metrics() {
+    std::env::set_var("DIFF_STORE_REQUEST_TIMEOUT_MS", "500");
+    let diff_store = MockDiffStore::start().unwrap();
+    std::env::set_var("DIFF_STORE_URL", diff_store.url());
+    let http_client = create_http_client().unwrap();
+    let request_size = DIFF_STORE_REQUEST_SIZE.with_label_values(&["POST"]);
+    let response_size = DIFF_STORE_RESPONSE_SIZE.with_label_values(&["POST"]);
+    let timeouts = DIFF_STORE_TIMEOUTS.with_label_values(&["POST"]);
+    let connection_errors = DIFF_STORE_CONNECTION_ERRORS.with_label_values(&["POST"]);
+
+    let (requests, request_bytes) = (request_size.get_sample_count(), request_size.get_sample_sum());
+    let (responses, ok) = (response_size.get_sample_count(), post_durations("200"));
+    update_diff_store(reasoned("measured", GRAPH), &http_client).await.unwrap();
+    assert_eq!(post_durations("200"), ok + 1);
+    assert_eq!(request_size.get_sample_count(), requests + 1);
+    assert!(request_size.get_sample_sum() > request_bytes + GRAPH.len() as f64);
+    assert_eq!(response_size.get_sample_count(), responses + 1);
+
+    let (timed_out, timeout_durations) = (timeouts.get(), post_durations("timeout"));
+    diff_store.delay_next(Duration::from_secs(2), 1);
+    let result = update_diff_store(reasoned("measured", GRAPH), &http_client).await;
+    assert!(matches!(result, Err(Error::Timeout(_))));
+    assert_eq!(timeouts.get(), timed_out + 1);
+    assert_eq!(post_durations("timeout"), timeout_durations + 1);
+
+    let (refused, refused_durations) = (connection_errors.get(), post_durations("connection_error"));
+    diff_store.stop().await;
+    // A new client, so that no pooled connection to the stopped mock is reused.
+    let http_client = create_http_client().unwrap();
+    assert!(update_diff_store(reasoned("measured", GRAPH), &http_client).await.is_err());
+    assert_eq!(connection_errors.get(), refused + 1);
+    assert_eq!(post_durations("connection_error"), refused_durations + 1);
+}
@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use fdk_rdf_postman::{
+    diff_store::{delete_graph_in_diff_store, get_graph, list_graph_ids},
+    error::Error,
+    mock_diff_store::MockDiffStore,
+};
+
+#[tokio::test]
+async fn injects_failures_and_delays() {
+    let diff_store = MockDiffStore::start().unwrap();
+    std::env::set_var("DIFF_STORE_URL", diff_store.url());
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_millis(200))
+        .build()
+        .unwrap();
+    diff_store.insert("1", "<a> <b> <c> .");
+
+    diff_store.fail_next(StatusCode::SERVICE_UNAVAILABLE, 1);
+    match list_graph_ids(&http_client).await {
+        Err(Error::SinkStatus { status, .. }) => assert_eq!(status.as_u16(), 503),
+        result => panic!("unexpected result {:?}", result),
+    }
+
+    diff_store.delay_next(Duration::from_millis(500), 1);
+    assert!(matches!(
+        get_graph("1", &http_client).await,
+        Err(Error::Timeout(_))
+    ));
+
+    assert_eq!(list_graph_ids(&http_client).await.unwrap(), vec!["1"]);
+    assert_eq!(
+        get_graph("1", &http_client).await.unwrap().as_deref(),
+        Some("<a> <b> <c> .")
+    );
+    delete_graph_in_diff_store("1", &http_client).await.unwrap();
+    diff_store.assert_absent("1");
+    assert_eq!(get_graph("1", &http_client).await.unwrap(), None);
+}
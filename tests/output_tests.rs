@@ -0,0 +1,28 @@
+use fdk_rdf_postman::{
+    coalesce::check_coalescing,
+    output::{check_transactional_id_prefix, transactional_id, Output},
+};
+
+#[test]
+fn transactional_ids_are_unique_per_postman_type_and_worker() {
+    assert_eq!(transactional_id("postman-0", 0), "postman-0-dataset-0");
+    assert_eq!(transactional_id("postman-0", 3), "postman-0-dataset-3");
+}
+
+#[test]
+fn transactions_require_a_transactional_id_prefix() {
+    std::env::set_var("KAFKA_TRANSACTIONS", "true");
+    std::env::remove_var("TRANSACTIONAL_ID_PREFIX");
+
+    assert!(check_transactional_id_prefix().is_err());
+    // No producer is created with an id that other instances may share.
+    assert!(Output::create(0).is_err());
+}
+
+#[test]
+fn transactions_can_not_be_coalesced() {
+    std::env::set_var("KAFKA_TRANSACTIONS", "true");
+    std::env::set_var("COALESCE_WINDOW_MS", "100");
+
+    assert!(check_coalescing().is_err());
+}
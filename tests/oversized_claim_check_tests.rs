@@ -0,0 +1,41 @@
+mod common;
+
+use fdk_rdf_postman::{
+    diff_store::{create_http_client, update_diff_store},
+    mock_diff_store::MockDiffStore,
+    rdf::parse_graph,
+};
+
+use common::reasoned;
+use oxrdfio::RdfFormat;
+
+#[tokio::test]
+async fn claim_checks_oversized_graphs() {
+    let diff_store = MockDiffStore::start().unwrap();
+    std::env::set_var("DIFF_STORE_URL", diff_store.url());
+    std::env::set_var("BLOB_STORE_URL", diff_store.blob_store_url());
+    std::env::set_var("MAX_GRAPH_BYTES", "200");
+    std::env::set_var("MAX_GRAPH_TRIPLES", "2");
+    std::env::set_var("OVERSIZED_GRAPH_POLICY", "claim_check");
+    let http_client = create_http_client().unwrap();
+
+    // Too many bytes is claim-checked as received, without parsing the graph.
+    let too_long = "not turtle ".repeat(20);
+    update_diff_store(reasoned("bytes", &too_long), &http_client).await.unwrap();
+    let stored = diff_store.graph("bytes").unwrap();
+    assert_eq!(stored.graph, None);
+    assert_eq!(diff_store.blob(&stored.graph_ref.unwrap()).unwrap(), too_long.as_bytes());
+
+    let triples = "<a:s> <a:p> <a:o1> . <a:s> <a:p> <a:o2> . <a:s> <a:p> <a:o3> .";
+    update_diff_store(reasoned("triples", triples), &http_client).await.unwrap();
+    let stored = diff_store.graph("triples").unwrap();
+    assert_eq!(stored.graph, None);
+    let blob = diff_store.blob(&stored.graph_ref.unwrap()).unwrap();
+    let blob = parse_graph(&String::from_utf8(blob).unwrap(), RdfFormat::Turtle).unwrap();
+    assert_eq!(blob.len(), 3);
+
+    update_diff_store(reasoned("small", "<a:s> <a:p> <a:o> ."), &http_client).await.unwrap();
+    let stored = diff_store.graph("small").unwrap();
+    assert!(stored.graph.is_some());
+    assert_eq!(stored.graph_ref, None);
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use fdk_rdf_postman::{
+    diff_store::{create_http_client, update_diff_store},
+    error::Error,
+    mock_diff_store::MockDiffStore,
+};
+
+use common::reasoned;
+
+#[tokio::test]
+async fn rejects_oversized_graphs() {
+    let diff_store = MockDiffStore::start().unwrap();
+    std::env::set_var("DIFF_STORE_URL", diff_store.url());
+    std::env::set_var("MAX_GRAPH_BYTES", "200");
+    std::env::set_var("MAX_GRAPH_TRIPLES", "2");
+    std::env::set_var("OVERSIZED_GRAPH_POLICY", "reject");
+    let http_client = create_http_client().unwrap();
+
+    // Too many bytes is rejected before parsing, so the invalid graph is not
+    // reported as a syntax error.
+    let result = update_diff_store(reasoned("bytes", &"not turtle ".repeat(20)), &http_client).await;
+    assert!(matches!(result, Err(Error::Validation(_))), "{:?}", result);
+
+    let triples = "<a:s> <a:p> <a:o1> . <a:s> <a:p> <a:o2> . <a:s> <a:p> <a:o3> .";
+    let result = update_diff_store(reasoned("triples", triples), &http_client).await;
+    assert!(matches!(result, Err(Error::Validation(_))), "{:?}", result);
+
+    update_diff_store(reasoned("small", "<a:s> <a:p> <a:o> ."), &http_client).await.unwrap();
+    assert!(diff_store.graph("small").is_some_and(|graph| graph.graph.is_some()));
+    diff_store.assert_absent("bytes");
+    diff_store.assert_absent("triples");
+    assert_eq!(diff_store.requests().len(), 1);
+}
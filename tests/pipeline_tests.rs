@@ -0,0 +1,252 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use fdk_rdf_postman::{
+    diff_store::create_http_client,
+    error::Error,
+    kafka::run_processor,
+    metrics::{DIFF_STORE_TIMEOUTS, PROCESSED_MESSAGES},
+    mock_diff_store::RecordedRequest,
+    output::Output,
+    schemas::{HarvestEvent, HarvestEventType},
+    source::{InMemorySource, MessageSource},
+};
+use rdkafka::Message;
+
+use common::{decoder, diff_store, event, GRAPH};
+
+fn request(method: &str, id: &str, status: u16) -> RecordedRequest {
+    RecordedRequest {
+        method: method.to_string(),
+        path: "/api/graphs".to_string(),
+        id: Some(id.to_string()),
+        idempotency_key: Some(format!("{}-1647698566000", id)),
+        traceparent: None,
+        status,
+    }
+}
+
+/// Diff store requests of this file time out after half a second, so that a
+/// delayed response times out. The timeout is read by the first client created.
+fn http_client() -> reqwest::Client {
+    std::env::set_var("DIFF_STORE_REQUEST_TIMEOUT_MS", "500");
+    create_http_client().unwrap()
+}
+
+/// Runs a processor over `events` until the source is exhausted.
+async fn process(events: &[HarvestEvent]) -> InMemorySource {
+    let source = InMemorySource::default();
+    for event in events {
+        source.push_event(event).unwrap();
+    }
+    let http_client = http_client();
+    run_processor(0, &source, &mut decoder(), &http_client, &Output::default())
+        .await
+        .unwrap();
+    source
+}
+
+#[tokio::test]
+async fn posts_reasoned_graphs() {
+    let diff_store = diff_store();
+    let source = process(&[event(HarvestEventType::DatasetReasoned, "posted", GRAPH)]).await;
+
+    assert_eq!(source.acked(), vec![0]);
+    diff_store.assert_stored("posted", GRAPH);
+    assert_eq!(
+        diff_store.requests_for("posted"),
+        vec![request("POST", "posted", 200)]
+    );
+}
+
+#[tokio::test]
+async fn retries_failed_deliveries_before_acknowledging() {
+    let diff_store = diff_store();
+    diff_store.fail_next_for("retried", StatusCode::INTERNAL_SERVER_ERROR, 1);
+    let source = process(&[
+        event(HarvestEventType::DatasetReasoned, "retried", GRAPH),
+        event(HarvestEventType::DatasetReasoned, "after-retried", GRAPH),
+    ])
+    .await;
+
+    assert_eq!(source.acked(), vec![0, 1]);
+    diff_store.assert_stored("retried", GRAPH);
+    diff_store.assert_stored("after-retried", GRAPH);
+    assert_eq!(
+        diff_store.requests_for("retried"),
+        vec![
+            request("POST", "retried", 500),
+            request("POST", "retried", 200)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn retries_timed_out_requests_before_acknowledging() {
+    let diff_store = diff_store();
+    let timeouts = DIFF_STORE_TIMEOUTS.with_label_values(&["POST"]).get();
+    diff_store.delay_next_for("timed-out", Duration::from_secs(2), 1);
+    let source = process(&[event(HarvestEventType::DatasetReasoned, "timed-out", GRAPH)]).await;
+
+    assert_eq!(source.acked(), vec![0]);
+    assert_eq!(
+        DIFF_STORE_TIMEOUTS.with_label_values(&["POST"]).get(),
+        timeouts + 1
+    );
+    diff_store.assert_stored("timed-out", GRAPH);
+}
+
+#[tokio::test]
+async fn any_2xx_without_body_is_a_successful_upsert() {
+    let diff_store = diff_store();
+    diff_store.respond_next_for("no-content", StatusCode::NO_CONTENT, 1);
+    let source = process(&[event(
+        HarvestEventType::DatasetReasoned,
+        "no-content",
+        GRAPH,
+    )])
+    .await;
+
+    assert_eq!(source.acked(), vec![0]);
+    diff_store.assert_stored("no-content", GRAPH);
+    assert_eq!(
+        diff_store.requests_for("no-content"),
+        vec![request("POST", "no-content", 204)]
+    );
+}
+
+#[tokio::test]
+async fn deletes_removed_graphs() {
+    let diff_store = diff_store();
+    diff_store.insert("removed", "<a> <b> <c> .");
+    let source = process(&[
+        event(HarvestEventType::DatasetRemoved, "removed", ""),
+        event(HarvestEventType::DatasetRemoved, "never-stored", ""),
+    ])
+    .await;
+
+    // Deleting a graph that is not stored succeeds.
+    assert_eq!(source.acked(), vec![0, 1]);
+    diff_store.assert_absent("removed");
+    assert_eq!(
+        diff_store.requests_for("removed"),
+        vec![request("DELETE", "removed", 200)]
+    );
+    assert_eq!(
+        diff_store.requests_for("never-stored"),
+        vec![request("DELETE", "never-stored", 404)]
+    );
+}
+
+#[tokio::test]
+async fn skips_events_without_diff_store_action() {
+    let diff_store = diff_store();
+    let source = process(&[event(
+        HarvestEventType::DatasetHarvested,
+        "harvested",
+        GRAPH,
+    )])
+    .await;
+
+    assert_eq!(source.acked(), vec![0]);
+    diff_store.assert_absent("harvested");
+    assert!(diff_store.requests_for("harvested").is_empty());
+}
+
+#[tokio::test]
+async fn skips_permanent_failures_without_dead_letter_topic() {
+    let diff_store = diff_store();
+    diff_store.fail_next_for("rejected", StatusCode::BAD_REQUEST, 1);
+    let source = InMemorySource::default();
+    source.push(None, Some(b"not avro".to_vec()), None);
+    source
+        .push_event(&event(HarvestEventType::DatasetReasoned, "rejected", GRAPH))
+        .unwrap();
+    source
+        .push_event(&event(
+            HarvestEventType::DatasetReasoned,
+            "after-rejected",
+            GRAPH,
+        ))
+        .unwrap();
+
+    let http_client = http_client();
+    run_processor(0, &source, &mut decoder(), &http_client, &Output::default())
+        .await
+        .unwrap();
+
+    // Neither message is retried, and the following event is applied.
+    assert_eq!(source.acked(), vec![0, 1, 2]);
+    diff_store.assert_absent("rejected");
+    assert_eq!(
+        diff_store.requests_for("rejected"),
+        vec![request("POST", "rejected", 400)]
+    );
+    diff_store.assert_stored("after-rejected", GRAPH);
+}
+
+#[tokio::test]
+async fn labels_undecodable_messages_as_undecoded() {
+    let processed = |action: &str| {
+        PROCESSED_MESSAGES
+            .with_label_values(&["error", "unknown", "dataset", action, "decode"])
+            .get()
+    };
+    let before = processed("undecoded");
+    let source = InMemorySource::default();
+    source.push(None, Some(b"not avro".to_vec()), None);
+
+    let http_client = http_client();
+    run_processor(0, &source, &mut decoder(), &http_client, &Output::default())
+        .await
+        .unwrap();
+
+    assert!(processed("undecoded") > before);
+    assert_eq!(processed("skip"), 0);
+}
+
+#[tokio::test]
+async fn failing_source_stops_the_processor() {
+    let diff_store = diff_store();
+    let source = InMemorySource::default();
+    source
+        .push_event(&event(
+            HarvestEventType::DatasetReasoned,
+            "before-failure",
+            GRAPH,
+        ))
+        .unwrap();
+    source.push_failure(Error::String("injected failure".to_string()));
+    source
+        .push_event(&event(
+            HarvestEventType::DatasetReasoned,
+            "after-failure",
+            GRAPH,
+        ))
+        .unwrap();
+
+    let http_client = http_client();
+    let result = run_processor(0, &source, &mut decoder(), &http_client, &Output::default()).await;
+    assert_eq!(result.unwrap_err().to_string(), "injected failure");
+    assert_eq!(source.acked(), vec![0]);
+    diff_store.assert_stored("before-failure", GRAPH);
+    diff_store.assert_absent("after-failure");
+}
+
+#[tokio::test]
+async fn rewound_messages_are_received_again() {
+    let source = InMemorySource::default();
+    source.push(None, Some(b"first".to_vec()), None);
+    source.push(None, Some(b"second".to_vec()), None);
+
+    let first = source.recv().await.unwrap().unwrap();
+    source.rewind(&first).unwrap();
+    let offsets = [
+        source.recv().await.unwrap().unwrap().offset(),
+        source.recv().await.unwrap().unwrap().offset(),
+    ];
+    assert_eq!(offsets, [0, 1]);
+    assert!(source.recv().await.unwrap().is_none());
+}
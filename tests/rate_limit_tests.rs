@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use fdk_rdf_postman::rate_limit::{parse_retry_after, Backoff, RateLimiter, RateLimits, TokenBucket};
+
+#[test]
+fn token_bucket_goes_into_debt() {
+    let bucket = TokenBucket::new(10.0, 2.0);
+    assert_eq!(bucket.reserve(1.0), Duration::ZERO);
+    assert_eq!(bucket.reserve(1.0), Duration::ZERO);
+
+    // Two tokens short at ten tokens per second.
+    let wait = bucket.reserve(2.0);
+    assert!(wait > Duration::from_millis(150) && wait <= Duration::from_millis(200), "{:?}", wait);
+}
+
+#[tokio::test]
+async fn limits_requests_in_flight() {
+    let limiter = RateLimiter::new(RateLimits {
+        max_in_flight: Some(1),
+        ..Default::default()
+    });
+
+    let permit = limiter.acquire(0).await;
+    let blocked = tokio::time::timeout(Duration::from_millis(50), limiter.acquire(0)).await;
+    assert!(blocked.is_err());
+
+    drop(permit);
+    let acquired = tokio::time::timeout(Duration::from_millis(50), limiter.acquire(0)).await;
+    assert!(acquired.is_ok());
+}
+
+#[tokio::test]
+async fn throttles_bytes_per_second() {
+    let limiter = RateLimiter::new(RateLimits {
+        bytes_per_second: Some(1000.0),
+        ..Default::default()
+    });
+
+    let start = std::time::Instant::now();
+    limiter.acquire(1000).await;
+    limiter.acquire(100).await;
+    assert!(start.elapsed() >= Duration::from_millis(90));
+}
+
+#[test]
+fn parses_retry_after() {
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1445412480);
+    assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+    assert_eq!(
+        parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+        Some(Duration::from_secs(30))
+    );
+    assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now), Some(Duration::ZERO));
+    assert_eq!(parse_retry_after("soon", now), None);
+}
+
+#[tokio::test]
+async fn backoff_pauses_until_the_longest_delay() {
+    let backoff = Backoff::new(Duration::from_millis(200));
+    backoff.back_off(Duration::from_millis(100));
+    assert_eq!(backoff.back_off(Duration::from_secs(60)), Duration::from_millis(200));
+    backoff.back_off(Duration::from_millis(10));
+    assert!(backoff.remaining() > Duration::from_millis(150));
+
+    let start = std::time::Instant::now();
+    backoff.wait().await;
+    assert!(start.elapsed() >= Duration::from_millis(150));
+    assert_eq!(backoff.remaining(), Duration::ZERO);
+}
@@ -0,0 +1,121 @@
+use fdk_rdf_postman::rdf::{convert_graph, isomorphic, parse_format, parse_graph, skolemize};
+use oxrdfio::RdfFormat;
+
+const TURTLE: &str = r#"
+@prefix dct: <http://purl.org/dc/terms/> .
+@prefix dcat: <http://www.w3.org/ns/dcat#> .
+
+<https://example.org/datasets/1> a dcat:Dataset ;
+    dct:title "Dataset"@en ;
+    dcat:contactPoint [ dct:identifier "contact" ] .
+"#;
+
+#[test]
+fn parses_format_names() {
+    assert_eq!(parse_format("turtle"), Some(RdfFormat::Turtle));
+    assert_eq!(parse_format("N-Triples"), Some(RdfFormat::NTriples));
+    assert_eq!(parse_format("application/n-triples"), Some(RdfFormat::NTriples));
+    assert!(matches!(parse_format("json-ld"), Some(RdfFormat::JsonLd { .. })));
+    assert_eq!(parse_format("unknown"), None);
+}
+
+#[test]
+fn converts_between_formats() {
+    let expected = parse_graph(TURTLE, RdfFormat::Turtle).unwrap();
+    for format in ["n-triples", "json-ld", "turtle"] {
+        let format = parse_format(format).unwrap();
+        let converted = convert_graph(TURTLE, RdfFormat::Turtle, format).unwrap();
+        let actual = parse_graph(&converted, format).unwrap();
+        assert!(isomorphic(&expected, &actual), "{}", converted);
+    }
+}
+
+#[test]
+fn rejects_invalid_input() {
+    assert!(convert_graph("not turtle", RdfFormat::Turtle, RdfFormat::NTriples).is_err());
+}
+
+#[test]
+fn skolemizes_blank_nodes_deterministically() {
+    let relabeled = TURTLE.replace(
+        "[ dct:identifier \"contact\" ]",
+        "_:b42 .\n_:b42 dct:identifier \"contact\"",
+    );
+    let base = "https://example.org/.well-known/genid/";
+    let first = skolemize(&parse_graph(TURTLE, RdfFormat::Turtle).unwrap(), base, "1").unwrap();
+    let second =
+        skolemize(&parse_graph(&relabeled, RdfFormat::Turtle).unwrap(), base, "1").unwrap();
+    let other_id =
+        skolemize(&parse_graph(TURTLE, RdfFormat::Turtle).unwrap(), base, "2").unwrap();
+
+    assert_eq!(first, second);
+    assert_ne!(first, other_id);
+    assert!(first
+        .iter()
+        .all(|triple| !triple.subject.is_blank_node() && !triple.object.is_blank_node()));
+}
+
+#[test]
+fn skolem_iris_are_pinned_to_first_degree_hashes() {
+    let base = "https://example.org/.well-known/genid/";
+    let skolemized =
+        skolemize(&parse_graph(TURTLE, RdfFormat::Turtle).unwrap(), base, "1").unwrap();
+
+    // The IRI of the blank node is the SHA-256 of the fdkId and the RDFC-1.0
+    // first degree hash of the node. A change of this IRI would make every
+    // skolemized graph in the diff store appear changed.
+    let expected = parse_graph(
+        r#"
+        @prefix dct: <http://purl.org/dc/terms/> .
+        @prefix dcat: <http://www.w3.org/ns/dcat#> .
+        @prefix genid: <https://example.org/.well-known/genid/> .
+
+        <https://example.org/datasets/1> a dcat:Dataset ;
+            dct:title "Dataset"@en ;
+            dcat:contactPoint genid:4285d3da5dcab2462428349d95f9f076260bfcf547ecb1135c6413d0155ee3f2 .
+        genid:4285d3da5dcab2462428349d95f9f076260bfcf547ecb1135c6413d0155ee3f2
+            dct:identifier "contact" .
+        "#,
+        RdfFormat::Turtle,
+    )
+    .unwrap();
+    assert_eq!(skolemized, expected);
+}
+
+#[test]
+fn skolem_iris_do_not_change_when_unrelated_blank_nodes_are_added() {
+    let base = "https://example.org/.well-known/genid/";
+    let turtle = r#"
+        @prefix dct: <http://purl.org/dc/terms/> .
+        @prefix dcat: <http://www.w3.org/ns/dcat#> .
+
+        <https://example.org/datasets/1> a dcat:Dataset ;
+            dcat:contactPoint [ dct:identifier "contact" ] ;
+            dcat:distribution [ dct:title "first" ], [ dct:title "second" ] ;
+            dct:temporal [ dcat:startDate "2020" ], [ dcat:startDate "2020" ] ;
+            dcat:qualifiedRelation [ dct:relation [ dct:title "a" ] ],
+                [ dct:relation [ dct:title "b" ] ] .
+    "#;
+    let extended = format!(
+        "{}\n<https://example.org/datasets/1> dct:spatial [ dct:identifier \"0301\" ] .",
+        turtle
+    );
+    let iris = |turtle: &str| -> Vec<String> {
+        let graph = parse_graph(turtle, RdfFormat::Turtle).unwrap();
+        let mut iris: Vec<String> = skolemize(&graph, base, "1")
+            .unwrap()
+            .iter()
+            .filter(|triple| triple.subject.to_string().starts_with(&format!("<{}", base)))
+            .map(|triple| triple.subject.to_string())
+            .collect();
+        iris.sort();
+        iris.dedup();
+        iris
+    };
+
+    let before = iris(turtle);
+    let after = iris(&extended);
+    assert_eq!(before.len(), 9);
+    assert_eq!(after.len(), 10);
+    assert!(before.iter().all(|iri| after.contains(iri)), "{:?}\n{:?}", before, after);
+}
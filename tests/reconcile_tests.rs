@@ -0,0 +1,169 @@
+mod common;
+
+use std::collections::HashMap;
+
+use actix_web::http::StatusCode;
+use fdk_rdf_postman::{
+    diff_store::{create_http_client, POSTMAN_TYPE},
+    error::Error,
+    kafka::MessageHandler,
+    metrics::RECONCILE_CHECK_FAILURES,
+    reconcile::{reconcile_events, EventReader, LatestEvent, LatestEvents},
+    schemas::{HarvestEvent, HarvestEventType},
+    source::{InMemorySource, MessageSource},
+};
+
+use common::{decoder, diff_store, event_at, GRAPH};
+
+const OLD_GRAPH: &str = "<https://example.org/datasets/1> <http://purl.org/dc/terms/title> \"Old\" .";
+
+/// Reconciliation compares the topic with every graph of the diff store, so
+/// tests sharing the mock diff store of this file run one at a time.
+static SERIAL: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Events of a single partition, readable by offset unless compacted away.
+struct Topic(Vec<Option<HarvestEvent>>);
+
+impl Topic {
+    fn new(events: Vec<HarvestEvent>) -> Self {
+        Self(events.into_iter().map(Some).collect())
+    }
+
+    fn compact(&mut self, offset: usize) {
+        self.0[offset] = None;
+    }
+
+    /// The latest event per fdkId, as found by scanning the topic.
+    async fn scan(&self) -> HashMap<String, LatestEvent> {
+        let source = InMemorySource::default();
+        for event in self.0.iter().flatten() {
+            source.push_event(event).unwrap();
+        }
+        let mut latest = LatestEvents::with_decoder(decoder(), None);
+        while let Some(message) = source.recv().await.unwrap() {
+            latest.handle(&message).await.unwrap();
+        }
+        latest.events
+    }
+}
+
+impl EventReader for Topic {
+    async fn read(&mut self, partition: i32, offset: i64) -> Result<HarvestEvent, Error> {
+        match self.0.get(offset as usize) {
+            Some(Some(event)) if partition == 0 => Ok(HarvestEvent {
+                event_type: event.event_type,
+                fdk_id: event.fdk_id.clone(),
+                graph: event.graph.clone(),
+                timestamp: event.timestamp,
+            }),
+            _ => Err(Error::String(format!("no event at offset {}", offset))),
+        }
+    }
+}
+
+fn check_failures() -> u64 {
+    ["other", "http_500"]
+        .iter()
+        .map(|class| {
+            RECONCILE_CHECK_FAILURES
+                .with_label_values(&[POSTMAN_TYPE.label(), class])
+                .get()
+        })
+        .sum()
+}
+
+#[tokio::test]
+async fn keeps_only_the_latest_actionable_event_per_fdk_id() {
+    let topic = Topic::new(vec![
+        event_at(HarvestEventType::DatasetReasoned, "latest", OLD_GRAPH, 1),
+        event_at(HarvestEventType::DatasetReasoned, "latest", GRAPH, 2),
+        event_at(HarvestEventType::DatasetHarvested, "latest", OLD_GRAPH, 3),
+        event_at(HarvestEventType::DatasetReasoned, "removed", GRAPH, 1),
+        event_at(HarvestEventType::DatasetRemoved, "removed", "", 2),
+    ]);
+    let latest = topic.scan().await;
+
+    assert_eq!(latest.len(), 2);
+    assert_eq!((latest["latest"].offset, latest["latest"].timestamp), (1, 2));
+    assert_eq!((latest["removed"].offset, latest["removed"].timestamp), (4, 2));
+}
+
+#[tokio::test]
+async fn finds_missing_stale_and_orphaned_graphs() {
+    let _serial = SERIAL.lock().await;
+    let diff_store = diff_store();
+    diff_store.insert("in-sync", GRAPH);
+    diff_store.insert("stale", OLD_GRAPH);
+    diff_store.insert("removed-but-stored", GRAPH);
+    diff_store.insert("unknown", GRAPH);
+    let mut topic = Topic::new(vec![
+        event_at(HarvestEventType::DatasetReasoned, "in-sync", GRAPH, 1),
+        event_at(HarvestEventType::DatasetReasoned, "stale", GRAPH, 1),
+        event_at(HarvestEventType::DatasetReasoned, "missing", GRAPH, 1),
+        event_at(HarvestEventType::DatasetRemoved, "removed-but-stored", "", 1),
+    ]);
+    let latest = topic.scan().await;
+
+    let http_client = create_http_client().unwrap();
+    let report = reconcile_events(&latest, &mut topic, false, &http_client).await.unwrap();
+
+    assert!(report.missing.contains("missing"));
+    assert!(report.stale.contains("stale"));
+    assert!(!report.stale.contains("in-sync"));
+    assert!(report.orphaned.contains("removed-but-stored"));
+    assert!(report.orphaned.contains("unknown"));
+    assert!(!report.orphaned.contains("in-sync"));
+    assert!(report.unchecked.is_empty());
+    assert_eq!(report.repaired, 0);
+    diff_store.assert_stored("stale", OLD_GRAPH);
+}
+
+#[tokio::test]
+async fn continues_past_graphs_that_can_not_be_compared() {
+    let _serial = SERIAL.lock().await;
+    let diff_store = diff_store();
+    diff_store.insert("unreadable-graph", GRAPH);
+    diff_store.insert("compacted-event", GRAPH);
+    diff_store.insert("stale-after-failures", OLD_GRAPH);
+    diff_store.fail_next_for("unreadable-graph", StatusCode::INTERNAL_SERVER_ERROR, 1);
+    let mut topic = Topic::new(vec![
+        event_at(HarvestEventType::DatasetReasoned, "unreadable-graph", GRAPH, 1),
+        event_at(HarvestEventType::DatasetReasoned, "compacted-event", GRAPH, 1),
+        event_at(HarvestEventType::DatasetReasoned, "stale-after-failures", GRAPH, 1),
+    ]);
+    let latest = topic.scan().await;
+    topic.compact(1);
+    let failures_before = check_failures();
+
+    let http_client = create_http_client().unwrap();
+    let report = reconcile_events(&latest, &mut topic, false, &http_client).await.unwrap();
+
+    assert_eq!(
+        report.unchecked.iter().map(String::as_str).collect::<Vec<_>>(),
+        vec!["compacted-event", "unreadable-graph"]
+    );
+    assert!(report.stale.contains("stale-after-failures"));
+    assert_eq!(check_failures() - failures_before, 2);
+}
+
+#[tokio::test]
+async fn repairs_differences_from_the_latest_events() {
+    let _serial = SERIAL.lock().await;
+    let diff_store = diff_store();
+    diff_store.insert("repaired-stale", OLD_GRAPH);
+    diff_store.insert("repaired-orphan", GRAPH);
+    let mut topic = Topic::new(vec![
+        event_at(HarvestEventType::DatasetReasoned, "repaired-missing", GRAPH, 1),
+        event_at(HarvestEventType::DatasetReasoned, "repaired-stale", GRAPH, 1),
+        event_at(HarvestEventType::DatasetRemoved, "repaired-orphan", "", 1),
+    ]);
+    let latest = topic.scan().await;
+
+    let http_client = create_http_client().unwrap();
+    let report = reconcile_events(&latest, &mut topic, true, &http_client).await.unwrap();
+
+    assert_eq!(report.repair_failures, 0);
+    diff_store.assert_stored("repaired-missing", GRAPH);
+    diff_store.assert_stored("repaired-stale", GRAPH);
+    diff_store.assert_absent("repaired-orphan");
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use std::path::PathBuf;
+
+use apache_avro::{Schema, Writer};
+use fdk_rdf_postman::{
+    diff_store::create_http_client,
+    replay::{read_events, replay},
+    schemas::HarvestEventType,
+};
+
+use common::{event, GRAPH};
+
+const DATASET_SCHEMA: &str = include_str!("../kafka/schemas/no.fdk.dataset.DatasetEvent.json");
+
+fn empty_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn fdk_ids(path: &std::path::Path) -> Vec<String> {
+    read_events(path)
+        .unwrap()
+        .map(|event| event.unwrap().fdk_id)
+        .collect()
+}
+
+#[test]
+fn reads_jsonl_events_skipping_blank_lines() {
+    let path = empty_dir("fdk-rdf-postman-replay-jsonl-test").join("events.jsonl");
+    let lines = [
+        serde_json::to_string(&event(HarvestEventType::DatasetReasoned, "first", GRAPH)).unwrap(),
+        String::new(),
+        serde_json::to_string(&event(HarvestEventType::DatasetRemoved, "second", GRAPH)).unwrap(),
+    ];
+    std::fs::write(&path, lines.join("\n")).unwrap();
+
+    assert_eq!(fdk_ids(&path), vec!["first", "second"]);
+}
+
+#[test]
+fn reads_events_past_invalid_lines() {
+    let path = empty_dir("fdk-rdf-postman-replay-invalid-test").join("events.jsonl");
+    let lines = [
+        serde_json::to_string(&event(HarvestEventType::DatasetReasoned, "first", GRAPH)).unwrap(),
+        "not an event".to_string(),
+        serde_json::to_string(&event(HarvestEventType::DatasetReasoned, "third", GRAPH)).unwrap(),
+    ];
+    std::fs::write(&path, lines.join("\n")).unwrap();
+
+    let events: Vec<_> = read_events(&path).unwrap().collect();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0].as_ref().unwrap().fdk_id, "first");
+    assert!(events[1].as_ref().unwrap_err().to_string().contains("line 2"));
+    assert_eq!(events[2].as_ref().unwrap().fdk_id, "third");
+}
+
+#[test]
+fn reads_avro_container_files() {
+    let path = empty_dir("fdk-rdf-postman-replay-avro-test").join("events.avro");
+    let schema = Schema::parse_str(DATASET_SCHEMA).unwrap();
+    let mut writer = Writer::new(&schema, Vec::new());
+    writer.append_ser(event(HarvestEventType::DatasetReasoned, "first", GRAPH)).unwrap();
+    writer.append_ser(event(HarvestEventType::DatasetRemoved, "second", GRAPH)).unwrap();
+    std::fs::write(&path, writer.into_inner().unwrap()).unwrap();
+
+    let events: Vec<_> = read_events(&path).unwrap().map(Result::unwrap).collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].fdk_id, "first");
+    assert_eq!(events[0].graph, GRAPH);
+    assert!(matches!(events[1].event_type, HarvestEventType::DatasetRemoved));
+}
+
+#[test]
+fn reads_turtle_files_named_by_fdk_id() {
+    let dir = empty_dir("fdk-rdf-postman-replay-turtle-test");
+    std::fs::write(dir.join("b.ttl"), GRAPH).unwrap();
+    std::fs::write(dir.join("a.ttl"), GRAPH).unwrap();
+    std::fs::write(dir.join("notes.txt"), "not a graph").unwrap();
+
+    let events: Vec<_> = read_events(&dir).unwrap().map(Result::unwrap).collect();
+    assert_eq!(
+        events.iter().map(|event| event.fdk_id.as_str()).collect::<Vec<_>>(),
+        vec!["a", "b"]
+    );
+    assert!(matches!(events[0].event_type, HarvestEventType::DatasetReasoned));
+    assert_eq!(events[0].graph, GRAPH);
+    assert!(events[0].timestamp > 0);
+}
+
+#[test]
+fn rejects_unsupported_files() {
+    let path = empty_dir("fdk-rdf-postman-replay-unsupported-test").join("events.csv");
+    std::fs::write(&path, "").unwrap();
+
+    assert!(read_events(&path).is_err());
+}
+
+#[tokio::test]
+async fn counts_unreadable_events_as_failed() {
+    let path = empty_dir("fdk-rdf-postman-replay-dry-run-test").join("events.jsonl");
+    let lines = [
+        serde_json::to_string(&event(HarvestEventType::DatasetReasoned, "first", GRAPH)).unwrap(),
+        "{}".to_string(),
+        serde_json::to_string(&event(HarvestEventType::DatasetRemoved, "first", GRAPH)).unwrap(),
+    ];
+    std::fs::write(&path, lines.join("\n")).unwrap();
+
+    let summary = replay(&path, true, &create_http_client().unwrap()).await.unwrap();
+    assert_eq!((summary.succeeded, summary.failed), (2, 1));
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use std::{net::TcpListener, time::Duration};
+
+use actix_web::{web, App, HttpResponse, HttpServer};
+
+use apache_avro::{to_avro_datum, to_value, Schema};
+use fdk_rdf_postman::{
+    mock_diff_store::MockDiffStore,
+    schema_cache::CachingAvroDecoder,
+    schemas::HarvestEvent,
+};
+use schema_registry_converter::{
+    async_impl::schema_registry::SrSettings, schema_registry_common::get_payload,
+};
+
+use common::reasoned;
+
+const DATASET_SCHEMA: &str = include_str!("../kafka/schemas/no.fdk.dataset.DatasetEvent.json");
+
+fn payload(id: u32) -> Vec<u8> {
+    let schema = Schema::parse_str(DATASET_SCHEMA).unwrap();
+    let event = reasoned("fdk-id", "<a> <b> <c> .");
+    get_payload(id, to_avro_datum(&schema, to_value(event).unwrap()).unwrap())
+}
+
+fn sr_settings(url: &str) -> SrSettings {
+    SrSettings::new_builder(url.to_string())
+        .set_timeout(Duration::from_millis(500))
+        .build()
+        .unwrap()
+}
+
+fn empty_cache_dir(name: &str) -> std::path::PathBuf {
+    let cache_dir = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    cache_dir
+}
+
+#[tokio::test]
+async fn decodes_with_seed_schema_only_for_known_ids() {
+    let cache_dir = empty_cache_dir("fdk-rdf-postman-schema-cache-seed-test");
+    // Nothing listens on port 1, so every registry call fails.
+    let sr_settings = sr_settings("http://127.0.0.1:1");
+    let mut decoder =
+        CachingAvroDecoder::with_cache_dir(sr_settings, &cache_dir).with_seed_schema_ids([7]);
+
+    let result = decoder.decode(Some(&payload(7))).await.unwrap();
+    assert_eq!(result.name.unwrap().fullname(None), "no.fdk.dataset.DatasetEvent");
+    assert!(decoder.decode(Some(&payload(8))).await.is_err());
+}
+
+#[tokio::test]
+async fn does_not_fall_back_when_registry_does_not_have_the_schema() {
+    let cache_dir = empty_cache_dir("fdk-rdf-postman-schema-cache-unknown-test");
+    std::fs::create_dir_all(&cache_dir).unwrap();
+    std::fs::write(cache_dir.join("7.avsc"), DATASET_SCHEMA).unwrap();
+    // The mock answers every registry call with 404.
+    let registry = MockDiffStore::start().unwrap();
+    let mut decoder = CachingAvroDecoder::with_cache_dir(sr_settings(registry.url()), &cache_dir)
+        .with_seed_schema_ids([7]);
+
+    assert!(decoder.decode(Some(&payload(7))).await.is_err());
+    registry.stop().await;
+}
+
+#[tokio::test]
+async fn decodes_from_cache_when_registry_is_unreachable() {
+    let cache_dir = std::env::temp_dir().join("fdk-rdf-postman-schema-cache-test");
+    std::fs::create_dir_all(&cache_dir).unwrap();
+    std::fs::write(cache_dir.join("42.avsc"), DATASET_SCHEMA).unwrap();
+
+    // Nothing listens on port 1, so every registry call fails.
+    let sr_settings = SrSettings::new_builder("http://127.0.0.1:1".to_string())
+        .set_timeout(Duration::from_millis(500))
+        .build()
+        .unwrap();
+    let mut decoder = CachingAvroDecoder::with_cache_dir(sr_settings, &cache_dir);
+
+    let event = reasoned("fdk-id", "<a> <b> <c> .");
+    let schema = Schema::parse_str(DATASET_SCHEMA).unwrap();
+    let datum = to_avro_datum(&schema, to_value(&event).unwrap()).unwrap();
+    let payload = get_payload(42, datum);
+
+    let result = decoder.decode(Some(&payload)).await.unwrap();
+    let decoded = apache_avro::from_value::<HarvestEvent>(&result.value).unwrap();
+
+    assert_eq!(result.name.unwrap().fullname(None), "no.fdk.dataset.DatasetEvent");
+    assert_eq!(decoded.fdk_id, event.fdk_id);
+    assert_eq!(decoded.graph, event.graph);
+}
+
+#[tokio::test]
+async fn fresh_decoder_decodes_with_schemas_persisted_before_the_registry_went_down() {
+    let cache_dir = empty_cache_dir("fdk-rdf-postman-schema-cache-restart-test");
+    let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let registry = HttpServer::new(|| {
+        App::new().route(
+            "/schemas/ids/{id}",
+            web::get().to(|| async {
+                HttpResponse::Ok().json(serde_json::json!({ "schema": DATASET_SCHEMA }))
+            }),
+        )
+    })
+    .shutdown_timeout(1)
+    .listen(listener)
+    .unwrap()
+    .run();
+    let registry_handle = registry.handle();
+    tokio::spawn(registry);
+
+    // The pod that fetched the schema from the registry persists it.
+    let mut decoder = CachingAvroDecoder::with_cache_dir(sr_settings(&url), &cache_dir);
+    decoder.decode(Some(&payload(9))).await.unwrap();
+    registry_handle.stop(true).await;
+
+    // Its replacement starts with no schemas in memory, and no seed schema
+    // ids, while the registry is down.
+    let mut decoder = CachingAvroDecoder::with_cache_dir(sr_settings(&url), &cache_dir)
+        .with_seed_schema_ids([]);
+    let result = decoder.decode(Some(&payload(9))).await.unwrap();
+    assert_eq!(result.name.unwrap().fullname(None), "no.fdk.dataset.DatasetEvent");
+}
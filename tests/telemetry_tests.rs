@@ -0,0 +1,136 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use fdk_rdf_postman::{
+    diff_store::create_http_client,
+    kafka::run_processor,
+    output::Output,
+    schemas::HarvestEventType,
+    source::{encode_event, InMemorySource},
+};
+use opentelemetry::{global, trace::TracerProvider};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use rdkafka::message::{Header, OwnedHeaders};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Subscriber,
+};
+use tracing_subscriber::{
+    layer::{Context, SubscriberExt},
+    registry::LookupSpan,
+    Layer,
+};
+
+use common::{decoder, diff_store, event, reasoned, GRAPH};
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const PARENT_ID: &str = "00f067aa0ba902b7";
+
+type SpanFields = HashMap<String, String>;
+
+/// Captures the fields of every `receive_message` span, in the order the
+/// spans were created, including fields recorded after creation.
+#[derive(Clone, Default)]
+struct ReceiveSpans(Arc<Mutex<Vec<SpanFields>>>);
+
+struct FieldVisitor<'a>(&'a mut SpanFields);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for ReceiveSpans {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != "receive_message" {
+            return;
+        }
+        let mut spans = self.0.lock().unwrap();
+        let mut fields = SpanFields::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        spans.push(fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(spans.len() - 1);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(index) = ctx.span(id).and_then(|span| span.extensions().get::<usize>().copied())
+        else {
+            return;
+        };
+        values.record(&mut FieldVisitor(&mut self.0.lock().unwrap()[index]));
+    }
+}
+
+#[tokio::test]
+async fn receive_spans_carry_the_partition_offset_and_fdk_id_of_the_message() {
+    let spans = ReceiveSpans::default();
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+
+    diff_store();
+    let source = InMemorySource::default();
+    for event in [
+        event(HarvestEventType::DatasetReasoned, "first-span", GRAPH),
+        event(HarvestEventType::DatasetRemoved, "second-span", GRAPH),
+    ] {
+        source.push_event(&event).unwrap();
+    }
+    let http_client = create_http_client().unwrap();
+    run_processor(0, &source, &mut decoder(), &http_client, &Output::default())
+        .await
+        .unwrap();
+
+    let spans = spans.0.lock().unwrap();
+    let fields: Vec<_> = spans
+        .iter()
+        .map(|span| (span["partition"].as_str(), span["offset"].as_str(), span["fdk_id"].as_str()))
+        .collect();
+    assert_eq!(fields, vec![("0", "0", "first-span"), ("0", "1", "second-span")]);
+    assert_eq!(spans[0]["event_type"], "dataset_reasoned");
+}
+
+#[tokio::test]
+async fn propagates_trace_context_from_kafka_headers_to_diff_store_requests() {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let provider = SdkTracerProvider::builder().build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("telemetry-tests")));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let diff_store = diff_store();
+    let source = InMemorySource::default();
+    let event = reasoned("traced", GRAPH);
+    let traceparent = format!("00-{}-{}-01", TRACE_ID, PARENT_ID);
+    let headers = OwnedHeaders::new().insert(Header {
+        key: "traceparent",
+        value: Some(&traceparent),
+    });
+    source.push(
+        Some(event.fdk_id.clone().into_bytes()),
+        Some(encode_event(&event, 1).unwrap()),
+        Some(headers),
+    );
+    let http_client = create_http_client().unwrap();
+    run_processor(0, &source, &mut decoder(), &http_client, &Output::default())
+        .await
+        .unwrap();
+
+    let requests = diff_store.requests_for("traced");
+    assert_eq!(requests.len(), 1);
+    // The request is sent in the trace of the message, from a span of its own.
+    let sent = requests[0].traceparent.as_deref().unwrap();
+    let fields: Vec<&str> = sent.split('-').collect();
+    assert_eq!(fields[1], TRACE_ID);
+    assert_ne!(fields[2], PARENT_ID);
+}
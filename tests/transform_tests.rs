@@ -0,0 +1,79 @@
+mod common;
+
+use fdk_rdf_postman::{
+    rdf::{isomorphic, parse_graph},
+    schemas::HarvestEventType,
+    transform::{TransformationConfig, TransformationPipeline},
+};
+use oxrdfio::RdfFormat;
+
+use common::event_at;
+
+fn pipeline(config: &str) -> TransformationPipeline {
+    let configs: Vec<TransformationConfig> = serde_json::from_str(config).unwrap();
+    TransformationPipeline::from_config(configs).unwrap()
+}
+
+fn transform(pipeline: &TransformationPipeline, input: &str, expected: &str) {
+    let event = event_at(HarvestEventType::DatasetReasoned, "123", input, 1700000000000);
+    let graph = parse_graph(input, RdfFormat::Turtle).unwrap();
+    let actual = pipeline.apply(graph, &event).unwrap();
+    let expected = parse_graph(expected, RdfFormat::Turtle).unwrap();
+    assert!(isomorphic(&actual, &expected), "{}", actual);
+}
+
+#[test]
+fn drops_predicates_and_rewrites_prefixes() {
+    let pipeline = pipeline(
+        r#"[
+            {"type": "drop_predicates", "predicates": ["http://example.org/internal"]},
+            {"type": "rewrite_iri_prefix", "from": "http://staging.example.org/", "to": "https://example.org/"}
+        ]"#,
+    );
+    transform(
+        &pipeline,
+        r#"
+        <http://staging.example.org/datasets/1> <http://example.org/internal> "secret" ;
+            <http://purl.org/dc/terms/publisher> <http://staging.example.org/orgs/1> .
+        "#,
+        r#"
+        <https://example.org/datasets/1> <http://purl.org/dc/terms/publisher> <https://example.org/orgs/1> .
+        "#,
+    );
+}
+
+#[test]
+fn adds_provenance() {
+    let pipeline = pipeline(r#"[{"type": "provenance", "base_iri": "https://example.org/records/"}]"#);
+    transform(
+        &pipeline,
+        "<https://example.org/datasets/1> a <http://www.w3.org/ns/dcat#Dataset> .",
+        r#"
+        <https://example.org/datasets/1> a <http://www.w3.org/ns/dcat#Dataset> .
+        <https://example.org/records/123> <http://purl.org/dc/terms/identifier> "123" ;
+            <http://www.w3.org/ns/prov#generatedAtTime> "2023-11-14T22:13:20Z"^^<http://www.w3.org/2001/XMLSchema#dateTime> .
+        "#,
+    );
+}
+
+#[test]
+fn applies_sparql_construct_and_update() {
+    let pipeline = pipeline(
+        r#"[
+            {"type": "sparql_construct", "query": "CONSTRUCT { ?s ?p ?o } WHERE { ?s ?p ?o FILTER(?p != <http://example.org/b>) }"},
+            {"type": "sparql_update", "update": "INSERT DATA { <http://example.org/s> <http://example.org/c> 3 }"}
+        ]"#,
+    );
+    transform(
+        &pipeline,
+        "<http://example.org/s> <http://example.org/a> 1 ; <http://example.org/b> 2 .",
+        "<http://example.org/s> <http://example.org/a> 1 ; <http://example.org/c> 3 .",
+    );
+}
+
+#[test]
+fn rejects_invalid_sparql() {
+    let configs: Vec<TransformationConfig> =
+        serde_json::from_str(r#"[{"type": "sparql_update", "update": "not sparql"}]"#).unwrap();
+    assert!(TransformationPipeline::from_config(configs).is_err());
+}
@@ -0,0 +1,284 @@
+use fdk_rdf_postman::{
+    rdf::parse_graph,
+    validation::{Severity, Shapes},
+};
+use oxrdfio::RdfFormat;
+
+const SHAPES: &str = r#"
+@prefix sh: <http://www.w3.org/ns/shacl#> .
+@prefix dct: <http://purl.org/dc/terms/> .
+@prefix dcat: <http://www.w3.org/ns/dcat#> .
+@prefix xsd: <http://www.w3.org/2001/XMLSchema#> .
+
+<https://example.org/shapes/Dataset> a sh:NodeShape ;
+    sh:targetClass dcat:Dataset ;
+    sh:property [
+        sh:path dct:title ;
+        sh:minCount 1 ;
+    ] , [
+        sh:path dct:issued ;
+        sh:maxCount 1 ;
+        sh:datatype xsd:date ;
+        sh:severity sh:Warning ;
+    ] , [
+        sh:path dcat:distribution ;
+        sh:class dcat:Distribution ;
+        sh:nodeKind sh:BlankNodeOrIRI ;
+        sh:message "distribution must be a dcat:Distribution" ;
+    ] .
+"#;
+
+fn shapes() -> Shapes {
+    Shapes::parse(&parse_graph(SHAPES, RdfFormat::Turtle).unwrap()).unwrap()
+}
+
+#[test]
+fn conforming_graph_has_no_results() {
+    let data = parse_graph(
+        r#"
+        @prefix dct: <http://purl.org/dc/terms/> .
+        @prefix dcat: <http://www.w3.org/ns/dcat#> .
+        @prefix xsd: <http://www.w3.org/2001/XMLSchema#> .
+
+        <https://example.org/datasets/1> a dcat:Dataset ;
+            dct:title "Dataset" ;
+            dct:issued "2024-01-01"^^xsd:date ;
+            dcat:distribution [ a dcat:Distribution ] .
+        "#,
+        RdfFormat::Turtle,
+    )
+    .unwrap();
+
+    let report = shapes().validate(&data);
+    assert!(report.conforms(), "{:?}", report.results);
+}
+
+#[test]
+fn reports_violations_and_warnings() {
+    let data = parse_graph(
+        r#"
+        @prefix dct: <http://purl.org/dc/terms/> .
+        @prefix dcat: <http://www.w3.org/ns/dcat#> .
+
+        <https://example.org/datasets/1> a dcat:Dataset ;
+            dct:issued "2024-01-01", "2024-01-02" ;
+            dcat:distribution "not a distribution" .
+        "#,
+        RdfFormat::Turtle,
+    )
+    .unwrap();
+
+    let report = shapes().validate(&data);
+    let mut constraints: Vec<_> = report
+        .results
+        .iter()
+        .map(|result| (result.constraint, result.severity))
+        .collect();
+    constraints.sort_by_key(|(constraint, _)| *constraint);
+    assert_eq!(
+        constraints,
+        vec![
+            ("ClassConstraintComponent", Severity::Violation),
+            ("DatatypeConstraintComponent", Severity::Warning),
+            ("DatatypeConstraintComponent", Severity::Warning),
+            ("MaxCountConstraintComponent", Severity::Warning),
+            ("MinCountConstraintComponent", Severity::Violation),
+            ("NodeKindConstraintComponent", Severity::Violation),
+        ]
+    );
+    assert_eq!(report.violations(), 3);
+    assert!(report
+        .results
+        .iter()
+        .any(|result| result.message == "distribution must be a dcat:Distribution"));
+
+    let report_graph = report.to_graph();
+    assert!(report_graph
+        .iter()
+        .any(|triple| triple.predicate.as_str() == "http://www.w3.org/ns/shacl#conforms"));
+}
+
+fn constraints(shapes: &str, data: &str) -> Vec<&'static str> {
+    let shapes = Shapes::parse(&parse_graph(shapes, RdfFormat::Turtle).unwrap()).unwrap();
+    let report = shapes.validate(&parse_graph(data, RdfFormat::Turtle).unwrap());
+    let mut constraints: Vec<_> = report.results.iter().map(|result| result.constraint).collect();
+    constraints.sort();
+    constraints
+}
+
+const PREFIXES: &str = r#"
+@prefix sh: <http://www.w3.org/ns/shacl#> .
+@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+@prefix dct: <http://purl.org/dc/terms/> .
+@prefix dcat: <http://www.w3.org/ns/dcat#> .
+@prefix foaf: <http://xmlns.com/foaf/0.1/> .
+@prefix ex: <https://example.org/> .
+"#;
+
+#[test]
+fn follows_subclasses_for_targets_and_class_constraints() {
+    let shapes = format!(
+        "{}{}",
+        PREFIXES,
+        r#"
+        ex:DatasetShape sh:targetClass dcat:Dataset ;
+            sh:property [ sh:path dct:publisher ; sh:class foaf:Agent ] ;
+            sh:property [ sh:path dct:title ; sh:minCount 1 ] .
+        "#
+    );
+    let data = format!(
+        "{}{}",
+        PREFIXES,
+        r#"
+        ex:Series rdfs:subClassOf dcat:Dataset .
+        foaf:Organization rdfs:subClassOf foaf:Agent .
+        ex:series a ex:Series ; dct:publisher ex:org .
+        ex:org a foaf:Organization .
+        "#
+    );
+    assert_eq!(constraints(&shapes, &data), vec!["MinCountConstraintComponent"]);
+}
+
+#[test]
+fn evaluates_complex_paths() {
+    let shapes = format!(
+        "{}{}",
+        PREFIXES,
+        r#"
+        ex:DatasetShape sh:targetClass dcat:Dataset ;
+            sh:property [ sh:path ( dcat:distribution dcat:accessURL ) ; sh:minCount 2 ] ;
+            sh:property [ sh:path [ sh:inversePath dcat:dataset ] ; sh:minCount 1 ] ;
+            sh:property [
+                sh:path [ sh:alternativePath ( dct:title dct:description ) ] ;
+                sh:maxCount 1
+            ] .
+        "#
+    );
+    let data = format!(
+        "{}{}",
+        PREFIXES,
+        r#"
+        ex:catalog dcat:dataset ex:dataset .
+        ex:dataset a dcat:Dataset ;
+            dct:title "Title" ;
+            dct:description "Description" ;
+            dcat:distribution [ dcat:accessURL ex:a ], [ dcat:accessURL ex:b ] .
+        "#
+    );
+    assert_eq!(constraints(&shapes, &data), vec!["MaxCountConstraintComponent"]);
+}
+
+#[test]
+fn checks_value_and_logical_constraints() {
+    let shapes = format!(
+        "{}{}",
+        PREFIXES,
+        r#"
+        ex:DatasetShape sh:targetClass dcat:Dataset ;
+            sh:pattern "^https://example.org/" ;
+            sh:property [ sh:path dct:identifier ; sh:pattern "^[0-9]+$" ; sh:maxLength 3 ] ;
+            sh:property [ sh:path dct:accessRights ; sh:in ( ex:public ex:restricted ) ] ;
+            sh:property [ sh:path dct:type ; sh:hasValue ex:dataset ] ;
+            sh:property [ sh:path dct:title ; sh:languageIn ( "nb" "en" ) ; sh:uniqueLang true ] ;
+            sh:property [
+                sh:path dct:publisher ;
+                sh:or ( [ sh:class foaf:Organization ] [ sh:class foaf:Person ] ) ;
+                sh:not [ sh:hasValue ex:nobody ] ;
+                sh:node ex:NamedShape
+            ] .
+        ex:NamedShape sh:property [ sh:path foaf:name ; sh:minCount 1 ] .
+        "#
+    );
+    let conforming = format!(
+        "{}{}",
+        PREFIXES,
+        r#"
+        ex:dataset a dcat:Dataset ;
+            dct:identifier "123" ;
+            dct:accessRights ex:public ;
+            dct:type ex:dataset ;
+            dct:title "Tittel"@nb, "Title"@en ;
+            dct:publisher ex:org .
+        ex:org a foaf:Organization ; foaf:name "Org" .
+        "#
+    );
+    assert_eq!(constraints(&shapes, &conforming), Vec::<&str>::new());
+
+    let violating = format!(
+        "{}{}",
+        PREFIXES,
+        r#"
+        <https://other.org/dataset> a dcat:Dataset ;
+            dct:identifier "12a4" ;
+            dct:accessRights ex:secret ;
+            dct:title "Tittel"@nb, "Annen tittel"@nb-NO, "Titre"@fr ;
+            dct:publisher ex:nobody .
+        "#
+    );
+    assert_eq!(
+        constraints(&shapes, &violating),
+        vec![
+            "HasValueConstraintComponent",
+            "InConstraintComponent",
+            "LanguageInConstraintComponent",
+            "MaxLengthConstraintComponent",
+            "NodeConstraintComponent",
+            "NotConstraintComponent",
+            "OrConstraintComponent",
+            "PatternConstraintComponent",
+            "PatternConstraintComponent",
+        ]
+    );
+}
+
+#[test]
+fn checks_closed_shapes_and_skips_deactivated_shapes() {
+    let shapes = format!(
+        "{}{}",
+        PREFIXES,
+        r#"
+        ex:DistributionShape sh:targetClass dcat:Distribution ;
+            sh:closed true ;
+            sh:ignoredProperties ( <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> ) ;
+            sh:property [ sh:path dcat:accessURL ] .
+        ex:DeactivatedShape sh:targetClass dcat:Distribution ;
+            sh:deactivated true ;
+            sh:property [ sh:path dct:title ; sh:minCount 1 ] .
+        "#
+    );
+    let data = format!(
+        "{}{}",
+        PREFIXES,
+        r#"
+        ex:distribution a dcat:Distribution ; dcat:accessURL ex:a ; dct:format "csv" .
+        "#
+    );
+    let shapes = Shapes::parse(&parse_graph(&shapes, RdfFormat::Turtle).unwrap()).unwrap();
+    let report = shapes.validate(&parse_graph(&data, RdfFormat::Turtle).unwrap());
+    assert_eq!(report.results.len(), 1);
+    assert_eq!(report.results[0].constraint, "ClosedConstraintComponent");
+    assert_eq!(
+        report.results[0].path.as_ref().map(|path| path.to_string()),
+        Some("<http://purl.org/dc/terms/format>".to_string())
+    );
+}
+
+#[test]
+fn rejects_every_unsupported_construct() {
+    let shapes = format!(
+        "{}{}",
+        PREFIXES,
+        r#"
+        ex:DatasetShape sh:targetClass dcat:Dataset ;
+            sh:sparql [ sh:select "SELECT $this WHERE {}" ] ;
+            sh:property [ sh:path dct:issued ; sh:lessThan dct:modified ] ;
+            sh:property [ sh:path dct:title ; sh:qualifiedValueShape [ sh:datatype dct:x ] ] .
+        "#
+    );
+    let error = Shapes::parse(&parse_graph(&shapes, RdfFormat::Turtle).unwrap())
+        .unwrap_err()
+        .to_string();
+    for construct in ["sh:sparql", "sh:lessThan", "sh:qualifiedValueShape"] {
+        assert!(error.contains(construct), "{} not in {}", construct, error);
+    }
+}